@@ -1,3 +1,4 @@
+use anyhow::anyhow;
 use aptos_logger::error;
 use aptos_sdk::types::account_address::AccountAddress;
 use serde::{Deserialize, Serialize};
@@ -29,6 +30,59 @@ pub struct JoinChatRoomRequest {
     pub full_message: String,
 }
 
+/// The query parameters the client attaches to the `/chat/<creator>/<collection>`
+/// WebSocket upgrade request. These carry the same proof of ownership that used to
+/// only be accepted as the first frame of an established connection, which let
+/// unauthenticated sockets sit open until the client got around to sending them.
+/// `chat_room_creator` and `chat_room_name` come from the path instead, since they're
+/// already part of the room being joined.
+///
+/// `chat_room_joiner`/`signature`/`full_message` and `session_token` are alternatives:
+/// a client presents the former trio the first time, then the latter on every
+/// reconnect after that until it expires (see [`crate::session::SessionTokens`]).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JoinChatRoomQuery {
+    /// The public key of the person requesting to join the chat room.
+    /// This should be a hex string representation of an account ed25519 public key.
+    pub chat_room_joiner: Option<String>,
+
+    /// See the field of the same name on [`JoinChatRoomRequest`].
+    pub signature: Option<String>,
+
+    /// See the field of the same name on [`JoinChatRoomRequest`].
+    pub full_message: Option<String>,
+
+    /// A token previously issued by [`crate::session::SessionTokens::issue`]. When
+    /// present and valid, this replaces the signature+indexer flow above entirely.
+    pub session_token: Option<String>,
+}
+
+impl JoinChatRoomQuery {
+    /// Reconstitutes a [`JoinChatRoomRequest`] by pairing the query params with the
+    /// path params, so the existing `authenticate_user` codepath can stay untouched.
+    /// Fails if the signature-based fields weren't provided, which is only expected
+    /// when the caller meant to authenticate via `session_token` instead.
+    pub fn into_request(
+        self,
+        chat_room_creator: AccountAddress,
+        chat_room_name: String,
+    ) -> Result<JoinChatRoomRequest, ApiError> {
+        Ok(JoinChatRoomRequest {
+            chat_room_creator,
+            chat_room_name,
+            chat_room_joiner: self
+                .chat_room_joiner
+                .ok_or_else(|| ApiError::BadRequest(anyhow!("Missing chat_room_joiner")))?,
+            signature: self
+                .signature
+                .ok_or_else(|| ApiError::BadRequest(anyhow!("Missing signature")))?,
+            full_message: self
+                .full_message
+                .ok_or_else(|| ApiError::BadRequest(anyhow!("Missing full_message")))?,
+        })
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ApiError {
     #[error("User is could submitted an invalid join request: {0:#}")]
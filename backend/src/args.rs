@@ -27,6 +27,57 @@ pub struct IndexerArgs {
     pub indexer_url: Url,
 }
 
+#[derive(Clone, Debug, Parser)]
+pub struct StorageArgs {
+    /// A SQLite connection URL (e.g. `sqlite://hong-bao.db`) used to persist feeds,
+    /// room metadata, and memberships across restarts.
+    #[clap(long, default_value = "sqlite://hong-bao.db")]
+    pub database_url: String,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct SessionArgs {
+    /// The HMAC secret used to sign resumable session tokens (see `crate::session`).
+    /// If unset, a random secret is generated at startup, which means sessions issued
+    /// before a restart stop working and every client has to fully re-authenticate.
+    #[clap(long)]
+    pub session_secret: Option<String>,
+
+    /// How long a session token stays valid before the client has to fully
+    /// re-authenticate (signature + indexer lookup) to get a new one.
+    #[clap(long, default_value_t = 3600)]
+    pub session_ttl_secs: u64,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct IrcArgs {
+    /// The address the IRC gateway listens on. This is a second, additive frontend
+    /// onto the same hubs the websocket server serves; IRC clients can connect here
+    /// and join a room as `#<collection>@<creator>` after a single-line SASL PLAIN
+    /// `AUTHENTICATE` (see `crate::irc::IrcGateway`).
+    #[clap(long, default_value = "0.0.0.0")]
+    pub irc_listen_address: String,
+
+    /// The port the IRC gateway listens on. Set to 0 to disable it.
+    #[clap(long, default_value_t = 6667)]
+    pub irc_listen_port: u16,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct ClusterArgs {
+    /// The base URL other nodes should use to reach this node's internal API. Used
+    /// as this node's identity within `--cluster-nodes`. If unset, clustering is
+    /// disabled and this node owns every hub it sees.
+    #[clap(long)]
+    pub this_node_url: Option<String>,
+
+    /// The base URLs of every node in the cluster, including this one. Ownership of
+    /// a hub is assigned by hashing its `(creator_address, collection_name)` across
+    /// this list, so it must be the same on every node.
+    #[clap(long)]
+    pub cluster_nodes: Vec<String>,
+}
+
 #[derive(Clone, Debug, Parser)]
 pub struct RootArgs {
     #[clap(flatten)]
@@ -37,4 +88,16 @@ pub struct RootArgs {
 
     #[clap(flatten)]
     pub indexer_args: IndexerArgs,
+
+    #[clap(flatten)]
+    pub storage_args: StorageArgs,
+
+    #[clap(flatten)]
+    pub irc_args: IrcArgs,
+
+    #[clap(flatten)]
+    pub session_args: SessionArgs,
+
+    #[clap(flatten)]
+    pub cluster_args: ClusterArgs,
 }
@@ -1,6 +1,8 @@
 use crate::{
-    api::{ApiError, JoinChatRoomRequest},
+    api::{ApiError, JoinChatRoomQuery, JoinChatRoomRequest},
     indexer::IndexerClient,
+    session::SessionTokens,
+    types::HubId,
 };
 use anyhow::Context;
 use aptos_logger::info;
@@ -52,18 +54,79 @@ pub async fn authenticate_user(
         .map_err(|err| ApiError::BadRequest(err.into()))?;
 
     // Confirm that the user has a token on their account that lets them join the chat room.
+    ensure_holds_token(
+        indexer_client,
+        account_address,
+        join_chat_room_request.chat_room_creator,
+        &join_chat_room_request.chat_room_name,
+    )
+    .await?;
+
+    info!(
+        room_joiner = join_chat_room_request.chat_room_joiner,
+        room_creator = join_chat_room_request.chat_room_creator,
+        room_name = join_chat_room_request.chat_room_name,
+        event = "authenticated"
+    );
+    Ok(account_address)
+}
+
+/// The entry point the `/chat` route actually calls: if `query` carries a
+/// `session_token`, this skips straight to verifying it instead of running the full
+/// `authenticate_user` flow, which is what makes reconnects cheap. Either way, it
+/// returns the session token the client should use next time — the same one it
+/// presented if it reconnected with one, or a freshly issued one after a full auth.
+/// Only the full-auth path mints a new token, so a token's expiry is a fixed deadline
+/// from when it was first issued, not a sliding window a holder could extend forever
+/// just by reconnecting before it lapses.
+pub async fn authenticate_user_with_session(
+    indexer_client: Arc<IndexerClient>,
+    session_tokens: Arc<SessionTokens>,
+    hub_id: &HubId,
+    chat_room_creator: AccountAddress,
+    chat_room_name: String,
+    query: JoinChatRoomQuery,
+) -> Result<(AccountAddress, String), ApiError> {
+    match &query.session_token {
+        Some(token) => {
+            let account_address = session_tokens
+                .verify(token, hub_id)
+                .map_err(|err| ApiError::NotRealAccountOwner(err.to_string()))?;
+            // Return the same token rather than reissuing: if every reconnect minted a
+            // fresh full-TTL token, expiry would become a sliding window instead of an
+            // absolute deadline, and a holder who has since sold/transferred the token
+            // would keep access forever by reconnecting within the TTL.
+            Ok((account_address, token.clone()))
+        }
+        None => {
+            let request = query.into_request(chat_room_creator, chat_room_name)?;
+            let account_address = authenticate_user(indexer_client, &request).await?;
+            let session_token = session_tokens.issue(account_address, hub_id);
+            Ok((account_address, session_token))
+        }
+    }
+}
+
+/// Confirms that `account_address` holds a token in `(creator_address, collection_name)`,
+/// i.e. that it is allowed into the gated room. Used both for the account joining a room
+/// itself and, for DM dialogs, for the peer the opener wants to talk to, who isn't present
+/// to prove account ownership but still has to be a legitimate member of the room.
+pub async fn ensure_holds_token(
+    indexer_client: Arc<IndexerClient>,
+    account_address: AccountAddress,
+    creator_address: AccountAddress,
+    collection_name: &str,
+) -> Result<(), ApiError> {
     let tokens_owned_by_account = indexer_client
         .get_tokens_on_account(&account_address)
         .await
         .map_err(|err| ApiError::BadRequest(err.into()))?;
-    let mut account_has_token = false;
-    for collection in tokens_owned_by_account.current_token_ownerships.into_iter() {
-        if collection.creator_address == join_chat_room_request.chat_room_creator.to_hex_literal()
-            && collection.collection_name == join_chat_room_request.chat_room_name
-        {
-            account_has_token = true;
-        }
-    }
+    let account_has_token = tokens_owned_by_account
+        .iter()
+        .any(|collection| {
+            collection.creator_address == creator_address.to_hex_literal()
+                && collection.collection_name == collection_name
+        });
 
     if !account_has_token {
         return Err(ApiError::DoesNotHoldChatRoomToken(
@@ -71,11 +134,5 @@ pub async fn authenticate_user(
         ));
     }
 
-    info!(
-        room_joiner = join_chat_room_request.chat_room_joiner,
-        room_creator = join_chat_room_request.chat_room_creator,
-        room_name = join_chat_room_request.chat_room_name,
-        event = "authenticated"
-    );
-    Ok(account_address)
+    Ok(())
 }
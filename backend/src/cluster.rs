@@ -0,0 +1,180 @@
+use crate::proto::{InputParcel, OutputParcel};
+use crate::types::HubId;
+use anyhow::Context;
+use aptos_sdk::types::account_address::AccountAddress;
+use reqwest::Client as HttpClient;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use tokio::sync::{broadcast, RwLock};
+
+const REMOTE_OUTPUT_CHANNEL_SIZE: usize = 16;
+
+/// A node in the cluster, identified by the base URL other nodes use to reach its
+/// internal API (see [`crate::server::Server::run`]'s `internal/*` routes).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeId(pub String);
+
+/// Read-only mapping of which node in the cluster owns each hub, so a connection for
+/// a given `(creator_address, collection_name)` always lands on the same node
+/// regardless of which node's `/chat` route the client happened to hit. Ownership is
+/// assigned by hashing the room identity, which keeps the mapping stable as long as
+/// the node list doesn't change; rebalancing on membership changes is out of scope
+/// here, same as the rest of this config-driven (not gossip-driven) cluster.
+#[derive(Clone, Debug)]
+pub struct ClusterMetadata {
+    this_node: NodeId,
+    nodes: Vec<NodeId>,
+}
+
+impl ClusterMetadata {
+    pub fn new(this_node: NodeId, mut nodes: Vec<NodeId>) -> Self {
+        if !nodes.contains(&this_node) {
+            nodes.push(this_node.clone());
+        }
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        ClusterMetadata { this_node, nodes }
+    }
+
+    /// A single-node "cluster", i.e. clustering is disabled and every hub is local.
+    pub fn standalone(this_node: NodeId) -> Self {
+        ClusterMetadata::new(this_node.clone(), vec![this_node])
+    }
+
+    pub fn owning_node(&self, creator_address: &AccountAddress, collection_name: &str) -> &NodeId {
+        let mut hasher = DefaultHasher::new();
+        creator_address.hash(&mut hasher);
+        collection_name.hash(&mut hasher);
+        let index = (hasher.finish() as usize) % self.nodes.len();
+        &self.nodes[index]
+    }
+
+    pub fn owns_locally(&self, creator_address: &AccountAddress, collection_name: &str) -> bool {
+        self.owning_node(creator_address, collection_name) == &self.this_node
+    }
+
+    /// Every other node in the cluster besides this one, i.e. who a hub's owner needs
+    /// to forward its outputs to so a client proxied through any of them still
+    /// receives live traffic. Empty in a standalone deployment.
+    pub fn peers(&self) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|node| *node != &self.this_node)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Node-to-node calls used when a client connects to a node that doesn't own the hub
+/// it wants to join.
+#[derive(Clone)]
+pub struct ClusterClient {
+    http: HttpClient,
+}
+
+impl Default for ClusterClient {
+    fn default() -> Self {
+        ClusterClient {
+            http: HttpClient::new(),
+        }
+    }
+}
+
+impl ClusterClient {
+    /// Proxies a client's input to the node that owns the hub.
+    pub async fn forward_input(
+        &self,
+        owner: &NodeId,
+        hub_id: &HubId,
+        input_parcel: &InputParcel,
+    ) -> anyhow::Result<()> {
+        self.http
+            .post(format!("{}/internal/hubs/{}/inputs", owner.0, hub_id.storage_key()))
+            .json(input_parcel)
+            .send()
+            .await
+            .context("Failed to forward input to owning node")?
+            .error_for_status()
+            .context("Owning node rejected forwarded input")?;
+        Ok(())
+    }
+
+    /// Tells the owning node that a client this node was proxying for `hub_id` has
+    /// disconnected, so its `Hub` drops it from `users` the same way a local
+    /// disconnect does. Without this, a client proxied through a non-owning node
+    /// would stay in the owner's member list (and keep being forwarded outputs)
+    /// forever once its socket closed. See `Server::process_remote_client`.
+    pub async fn forward_disconnect(
+        &self,
+        owner: &NodeId,
+        hub_id: &HubId,
+        client_address: AccountAddress,
+    ) -> anyhow::Result<()> {
+        self.http
+            .post(format!(
+                "{}/internal/hubs/{}/disconnect",
+                owner.0,
+                hub_id.storage_key()
+            ))
+            .json(&client_address)
+            .send()
+            .await
+            .context("Failed to forward disconnect to owning node")?
+            .error_for_status()
+            .context("Owning node rejected forwarded disconnect")?;
+        Ok(())
+    }
+
+    /// Called by the owning node to push one of its `Hub`'s outputs out to a peer, so
+    /// that peer's `Broadcasting` can re-emit it to any client it's proxying for this
+    /// hub. See `Server::forward_hub_outputs`.
+    pub async fn forward_output(
+        &self,
+        peer: &NodeId,
+        hub_id: &HubId,
+        output_parcel: &OutputParcel,
+    ) -> anyhow::Result<()> {
+        self.http
+            .post(format!("{}/internal/hubs/{}/outputs", peer.0, hub_id.storage_key()))
+            .json(output_parcel)
+            .send()
+            .await
+            .context("Failed to forward output to peer node")?
+            .error_for_status()
+            .context("Peer node rejected forwarded output")?;
+        Ok(())
+    }
+}
+
+/// Lets a non-owning node subscribe to the `OutputParcel`s an owning node broadcasts
+/// for a given hub, and re-emit them locally to that node's own connected clients.
+/// This is the other half of the bridge `ClusterClient::forward_input` completes:
+/// inputs flow proxying-node -> owner, outputs flow owner -> proxying-node.
+#[derive(Default)]
+pub struct Broadcasting {
+    remote_outputs: RwLock<HashMap<HubId, broadcast::Sender<OutputParcel>>>,
+}
+
+impl Broadcasting {
+    /// Called on a non-owning node to get (creating if necessary) the channel that
+    /// outputs forwarded from the owning node get published to.
+    pub async fn subscribe(&self, hub_id: &HubId) -> broadcast::Receiver<OutputParcel> {
+        if let Some(sender) = self.remote_outputs.read().await.get(hub_id) {
+            return sender.subscribe();
+        }
+        let mut remote_outputs = self.remote_outputs.write().await;
+        let sender = remote_outputs
+            .entry(hub_id.clone())
+            .or_insert_with(|| broadcast::channel(REMOTE_OUTPUT_CHANNEL_SIZE).0);
+        sender.subscribe()
+    }
+
+    /// Called by the internal inbound route when the owning node forwards an output
+    /// parcel meant for one of this node's locally-connected clients.
+    pub async fn publish(&self, hub_id: &HubId, output_parcel: OutputParcel) {
+        if let Some(sender) = self.remote_outputs.read().await.get(hub_id) {
+            // No local subscribers left for this hub; ignore the send error.
+            let _ = sender.send(output_parcel);
+        }
+    }
+}
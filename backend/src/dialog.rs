@@ -0,0 +1,178 @@
+use crate::model::feed::Feed;
+use crate::model::message::Message;
+use crate::model::user::User;
+use crate::proto::{
+    DialogOpenedOutput, DialogPostedOutput, Input, InputParcel, MessageOutput, OpenDialogInput,
+    Output, OutputError, OutputParcel, PostDialogInput, UserOutput,
+};
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::Utc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{broadcast, RwLock};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
+
+const OUTPUT_CHANNEL_SIZE: usize = 16;
+const MAX_MESSAGE_BODY_LENGTH: usize = 256;
+
+/// The unordered pair of the two participants in a dialog; `(a, b)` and `(b, a)`
+/// identify the same dialog.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DialogKey(AccountAddress, AccountAddress);
+
+impl DialogKey {
+    pub fn new(a: AccountAddress, b: AccountAddress) -> Self {
+        if a.to_hex_literal() <= b.to_hex_literal() {
+            DialogKey(a, b)
+        } else {
+            DialogKey(b, a)
+        }
+    }
+}
+
+/// A private conversation between two token holders, broadcasting only to those two
+/// addresses. This mirrors `Hub`, but there's no room to join or leave: the two
+/// participants are fixed for the dialog's lifetime, so there's no `send_ignored` and
+/// no membership set to maintain.
+pub struct Dialog {
+    output_sender: broadcast::Sender<OutputParcel>,
+    participants: (AccountAddress, AccountAddress),
+    feed: RwLock<Feed>,
+}
+
+impl Dialog {
+    fn new(participants: (AccountAddress, AccountAddress)) -> Self {
+        let (output_sender, _) = broadcast::channel(OUTPUT_CHANNEL_SIZE);
+        Dialog {
+            output_sender,
+            participants,
+            feed: RwLock::new(Feed::default()),
+        }
+    }
+
+    pub async fn run(&self, receiver: UnboundedReceiver<InputParcel>) {
+        let receiver = UnboundedReceiverStream::new(receiver);
+        receiver
+            .for_each(|input_parcel| self.process(input_parcel))
+            .await;
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OutputParcel> {
+        self.output_sender.subscribe()
+    }
+
+    fn peer_of(&self, client_address: AccountAddress) -> Option<AccountAddress> {
+        if self.participants.0 == client_address {
+            Some(self.participants.1)
+        } else if self.participants.1 == client_address {
+            Some(self.participants.0)
+        } else {
+            None
+        }
+    }
+
+    async fn process(&self, input_parcel: InputParcel) {
+        match input_parcel.input {
+            Input::OpenDialog(input) => {
+                self.process_open(input_parcel.client_address, input).await
+            }
+            Input::PostDialog(input) => {
+                self.process_post(input_parcel.client_address, input).await
+            }
+            // Group-chat inputs sent over a `/dm` socket are ignored.
+            _ => {}
+        }
+    }
+
+    async fn process_open(&self, client_address: AccountAddress, _input: OpenDialogInput) {
+        let Some(peer_address) = self.peer_of(client_address) else {
+            return;
+        };
+        let messages = self
+            .feed
+            .read()
+            .await
+            .messages_iter()
+            .map(|message| {
+                MessageOutput::new(
+                    message.id,
+                    UserOutput::new(message.user.address),
+                    &message.body,
+                    message.created_at,
+                )
+            })
+            .collect();
+        self.send(
+            client_address,
+            Output::DialogOpened(DialogOpenedOutput::new(
+                UserOutput::new(peer_address),
+                messages,
+            )),
+        );
+    }
+
+    async fn process_post(&self, client_address: AccountAddress, input: PostDialogInput) {
+        let Some(peer_address) = self.peer_of(client_address) else {
+            return;
+        };
+
+        if input.body.is_empty() || input.body.len() > MAX_MESSAGE_BODY_LENGTH {
+            self.send(client_address, Output::Error(OutputError::InvalidMessageBody));
+            return;
+        }
+
+        let user = User::new(client_address);
+        let message = Message::new(Uuid::new_v4(), user, &input.body, Utc::now());
+        self.feed.write().await.add_message(message.clone());
+
+        let message_output = MessageOutput::new(
+            message.id,
+            UserOutput::new(message.user.address),
+            &message.body,
+            message.created_at,
+        );
+        // Both participants see every message, tagged with the peer address from
+        // their own point of view so a client juggling several dialogs can tell them
+        // apart.
+        self.send(
+            client_address,
+            Output::DialogPosted(DialogPostedOutput::new(peer_address, message_output.clone())),
+        );
+        self.send(
+            peer_address,
+            Output::DialogPosted(DialogPostedOutput::new(client_address, message_output)),
+        );
+    }
+
+    fn send(&self, client_address: AccountAddress, output: Output) {
+        if self.output_sender.receiver_count() > 0 {
+            self.output_sender
+                .send(OutputParcel::new(client_address, output))
+                .unwrap();
+        }
+    }
+}
+
+/// All currently-open dialogs, keyed by the unordered pair of participants.
+#[derive(Default)]
+pub struct DialogRegistry {
+    dialogs: RwLock<HashMap<DialogKey, Arc<Dialog>>>,
+}
+
+impl DialogRegistry {
+    pub async fn get_or_create(&self, a: AccountAddress, b: AccountAddress) -> Arc<Dialog> {
+        let key = DialogKey::new(a, b);
+        if let Some(dialog) = self.dialogs.read().await.get(&key) {
+            return dialog.clone();
+        }
+        self.dialogs
+            .write()
+            .await
+            .entry(key)
+            .or_insert_with(|| Arc::new(Dialog::new((a, b))))
+            .clone()
+    }
+}
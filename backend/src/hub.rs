@@ -2,13 +2,17 @@ use crate::model::feed::Feed;
 use crate::model::message::Message;
 use crate::model::user::User;
 use crate::proto::{
-    Input, InputParcel, JoinInput, JoinedOutput, MessageOutput, Output, OutputError, OutputParcel,
-    PostInput, PostedOutput, UserJoinedOutput, UserLeftOutput, UserOutput, UserPostedOutput,
+    HistoryInput, HistoryOutput, HistoryReference, HistorySubcommand, Input, InputParcel,
+    JoinInput, JoinedOutput, MessageOutput, Output, OutputError, OutputParcel, PostInput,
+    PostedOutput, UserJoinedOutput, UserLeftOutput, UserOutput, UserPostedOutput,
 };
+use crate::storage::Storage;
+use aptos_logger::error;
 use aptos_sdk::types::account_address::AccountAddress;
 use chrono::Utc;
 use futures::StreamExt;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::{broadcast, RwLock};
@@ -18,10 +22,32 @@ use uuid::Uuid;
 
 const OUTPUT_CHANNEL_SIZE: usize = 16;
 const MAX_MESSAGE_BODY_LENGTH: usize = 256;
+const MAX_HISTORY_LIMIT: usize = 100;
+const DEFAULT_JOIN_HISTORY_LIMIT: usize = 50;
 
-#[derive(Clone, Copy, Default)]
+#[derive(Default)]
 pub struct HubOptions {
     pub alive_interval: Option<Duration>,
+
+    /// When set, the hub write-through persists messages and memberships to `storage`
+    /// and is hydrated from `initial_feed` at construction time, keyed by
+    /// `(creator_address, collection_name)`.
+    pub persistence: Option<HubPersistence>,
+}
+
+pub struct HubPersistence {
+    pub storage: Arc<Storage>,
+    pub creator_address: AccountAddress,
+    pub collection_name: String,
+    pub initial_feed: Feed,
+}
+
+// The subset of `HubPersistence` a `Hub` needs to keep around for the lifetime of the
+// room, i.e. everything except the one-time hydration payload.
+struct RoomKey {
+    storage: Arc<Storage>,
+    creator_address: AccountAddress,
+    collection_name: String,
 }
 
 // A Hub is a single chat room.
@@ -30,16 +56,29 @@ pub struct Hub {
     output_sender: broadcast::Sender<OutputParcel>,
     users: RwLock<HashMap<AccountAddress, User>>,
     feed: RwLock<Feed>,
+    persistence: Option<RoomKey>,
 }
 
 impl Hub {
     pub fn new(options: HubOptions) -> Self {
         let (output_sender, _) = broadcast::channel(OUTPUT_CHANNEL_SIZE);
+        let (feed, persistence) = match options.persistence {
+            Some(persistence) => (
+                persistence.initial_feed,
+                Some(RoomKey {
+                    storage: persistence.storage,
+                    creator_address: persistence.creator_address,
+                    collection_name: persistence.collection_name,
+                }),
+            ),
+            None => (Feed::default(), None),
+        };
         Hub {
             alive_interval: options.alive_interval,
             output_sender,
             users: Default::default(),
-            feed: Default::default(),
+            feed: RwLock::new(feed),
+            persistence,
         }
     }
 
@@ -57,6 +96,21 @@ impl Hub {
         self.output_sender.subscribe()
     }
 
+    /// Feeds a single `InputParcel` straight into the hub, the same way `run`'s
+    /// per-connection channel does. Called for an `InputParcel` a non-owning node in
+    /// the cluster forwarded over `/internal/hubs/:key/inputs`, which has no
+    /// connection or channel of its own on this (the owning) node.
+    pub async fn process_external(&self, input_parcel: InputParcel) {
+        self.process(input_parcel).await;
+    }
+
+    /// The addresses currently joined to the room, e.g. for `IrcGateway`'s `WHO`
+    /// handler, which (unlike `Input`/`Output` traffic) answers a client directly
+    /// without going through the broadcast channel.
+    pub async fn users(&self) -> Vec<AccountAddress> {
+        self.users.read().await.keys().copied().collect()
+    }
+
     pub async fn on_disconnect(&self, client_address: AccountAddress) {
         // Remove user on disconnect
         if self.users.write().await.remove(&client_address).is_some() {
@@ -72,6 +126,12 @@ impl Hub {
         match input_parcel.input {
             Input::Join(input) => self.process_join(input_parcel.client_address, input).await,
             Input::Post(input) => self.process_post(input_parcel.client_address, input).await,
+            Input::History(input) => {
+                self.process_history(input_parcel.client_address, input).await
+            }
+            // A `Hub` only understands group-chat inputs; dialogs are handled by
+            // `crate::dialog::Dialog` instead.
+            Input::OpenDialog(_) | Input::PostDialog(_) => {}
         }
     }
 
@@ -82,6 +142,16 @@ impl Hub {
             .await
             .insert(client_address, user.clone());
 
+        if let Some(room) = &self.persistence {
+            if let Err(err) = room
+                .storage
+                .record_membership(&room.creator_address, &room.collection_name, &client_address)
+                .await
+            {
+                error!(error = ?err, event = "persist_membership_failed");
+            }
+        }
+
         // Report success to user
         let user_output = UserOutput::new(client_address);
         let other_users = self
@@ -97,20 +167,11 @@ impl Hub {
                 }
             })
             .collect();
-        let messages = self
-            .feed
-            .read()
-            .await
-            .messages_iter()
-            .map(|message| {
-                MessageOutput::new(
-                    message.id,
-                    UserOutput::new(message.user.address),
-                    &message.body,
-                    message.created_at,
-                )
-            })
-            .collect();
+        // Rather than dumping the whole feed, which grows unbounded for long-lived
+        // rooms, a joining client only gets the latest page of history. They can
+        // follow up with an `Input::History` query (e.g. `BEFORE` the oldest message
+        // here) to page back further.
+        let (messages, _has_more) = self.latest_messages(DEFAULT_JOIN_HISTORY_LIMIT).await;
         self.send_targeted(
             client_address,
             Output::Joined(JoinedOutput::new(
@@ -144,6 +205,20 @@ impl Hub {
         }
 
         let message = Message::new(Uuid::new_v4(), user.clone(), &input.body, Utc::now());
+
+        // Write through to storage before broadcasting, so a crash after the broadcast
+        // can't lose a message clients have already seen. A failed write is only
+        // logged; the message is still broadcast.
+        if let Some(room) = &self.persistence {
+            if let Err(err) = room
+                .storage
+                .record_message(&room.creator_address, &room.collection_name, &message)
+                .await
+            {
+                error!(error = ?err, event = "persist_message_failed");
+            }
+        }
+
         self.feed.write().await.add_message(message.clone());
 
         let message_output = MessageOutput::new(
@@ -165,6 +240,117 @@ impl Hub {
         .await;
     }
 
+    async fn process_history(&self, client_address: AccountAddress, input: HistoryInput) {
+        if self.users.read().await.get(&client_address).is_none() {
+            self.send_error(client_address, OutputError::NotJoined);
+            return;
+        }
+
+        let (messages, has_more) = match input.subcommand {
+            HistorySubcommand::Latest { limit } => {
+                self.latest_messages(limit.unwrap_or(DEFAULT_JOIN_HISTORY_LIMIT)).await
+            }
+            HistorySubcommand::Before { reference, limit } => {
+                self.messages_before(reference, limit.unwrap_or(DEFAULT_JOIN_HISTORY_LIMIT)).await
+            }
+            HistorySubcommand::After { reference, limit } => {
+                self.messages_after(reference, limit.unwrap_or(DEFAULT_JOIN_HISTORY_LIMIT)).await
+            }
+            HistorySubcommand::Between { start, end } => self.messages_between(start, end).await,
+        };
+
+        self.send_targeted(client_address, Output::History(HistoryOutput::new(messages, has_more)));
+    }
+
+    // The methods below all work off the same in-memory, chronologically-ordered
+    // snapshot of the feed. `Feed` doesn't expose random access, so this is O(n) in
+    // the size of the room's history rather than the cheap range lookup a proper
+    // index would give us, but it keeps `Message`/`Feed` unaware of pagination.
+    async fn ordered_messages(&self) -> Vec<MessageOutput> {
+        self.feed
+            .read()
+            .await
+            .messages_iter()
+            .map(|message| {
+                MessageOutput::new(
+                    message.id,
+                    UserOutput::new(message.user.address),
+                    &message.body,
+                    message.created_at,
+                )
+            })
+            .collect()
+    }
+
+    async fn latest_messages(&self, limit: usize) -> (Vec<MessageOutput>, bool) {
+        let limit = limit.clamp(1, MAX_HISTORY_LIMIT);
+        let all = self.ordered_messages().await;
+        let has_more = all.len() > limit;
+        let start = all.len().saturating_sub(limit);
+        (all[start..].to_vec(), has_more)
+    }
+
+    async fn messages_before(
+        &self,
+        reference: HistoryReference,
+        limit: usize,
+    ) -> (Vec<MessageOutput>, bool) {
+        let limit = limit.clamp(1, MAX_HISTORY_LIMIT);
+        let all = self.ordered_messages().await;
+        let end = all
+            .iter()
+            .position(|message| matches_reference(message, &reference))
+            .unwrap_or(all.len());
+        let start = end.saturating_sub(limit);
+        let has_more = start > 0;
+        (all[start..end].to_vec(), has_more)
+    }
+
+    async fn messages_after(
+        &self,
+        reference: HistoryReference,
+        limit: usize,
+    ) -> (Vec<MessageOutput>, bool) {
+        let limit = limit.clamp(1, MAX_HISTORY_LIMIT);
+        let all = self.ordered_messages().await;
+        let start = all
+            .iter()
+            .position(|message| matches_reference(message, &reference))
+            .map(|index| index + 1)
+            .unwrap_or(all.len());
+        let end = (start + limit).min(all.len());
+        let has_more = end < all.len();
+        (all[start..end].to_vec(), has_more)
+    }
+
+    async fn messages_between(
+        &self,
+        start: HistoryReference,
+        end: HistoryReference,
+    ) -> (Vec<MessageOutput>, bool) {
+        let all = self.ordered_messages().await;
+        let start_index = all
+            .iter()
+            .position(|message| matches_reference(message, &start))
+            .unwrap_or(0);
+        let end_index = all
+            .iter()
+            .position(|message| matches_reference(message, &end))
+            .map(|index| index + 1)
+            .unwrap_or(all.len());
+        if start_index >= end_index {
+            return (Vec::new(), false);
+        }
+        let slice = &all[start_index..end_index];
+        let has_more = slice.len() > MAX_HISTORY_LIMIT;
+        let slice = if has_more {
+            &slice[..MAX_HISTORY_LIMIT]
+        } else {
+            slice
+        };
+        (slice.to_vec(), has_more)
+    }
+
     async fn tick_alive(&self) {
         let alive_interval = if let Some(alive_interval) = self.alive_interval {
             alive_interval
@@ -223,6 +409,13 @@ impl Default for Hub {
     }
 }
 
+fn matches_reference(message: &MessageOutput, reference: &HistoryReference) -> bool {
+    match reference {
+        HistoryReference::MessageId(id) => message.id == *id,
+        HistoryReference::Timestamp(created_at) => message.created_at == *created_at,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use aptos_sdk::types::account_address::AccountAddress;
@@ -2,6 +2,24 @@ use anyhow::Result;
 use aptos_sdk::types::account_address::AccountAddress;
 use graphql_client::{GraphQLQuery, Response};
 use reqwest::{Client as ReqwestClient, Url};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+/// How many ownerships to request per page. The indexer returns exactly this many
+/// rows when there are more to fetch, so a short page is how we know we've reached
+/// the end.
+const TOKEN_OWNERSHIP_PAGE_SIZE: i64 = 100;
+
+/// How long a cached ownership lookup is trusted before we hit the indexer again.
+/// Kept short since losing access to a room (token sold/transferred) should be
+/// noticed reasonably quickly, not just at next process restart.
+const TOKEN_OWNERSHIP_CACHE_TTL: Duration = Duration::from_secs(30);
+
+struct CachedOwnership {
+    ownerships: Arc<Vec<tokens_owned_by_account::TokensOwnedByAccountCurrentTokenOwnerships>>,
+    fetched_at: Instant,
+}
 
 #[derive(Debug)]
 pub struct IndexerClient {
@@ -10,6 +28,10 @@ pub struct IndexerClient {
 
     // The reqwest client.
     pub client: ReqwestClient,
+
+    // Caches the result of `get_tokens_on_account` for a short while, since it's
+    // called on every join and every DM, and the underlying indexer query is paged.
+    ownership_cache: RwLock<HashMap<AccountAddress, CachedOwnership>>,
 }
 
 impl IndexerClient {
@@ -17,38 +39,78 @@ impl IndexerClient {
         IndexerClient {
             url,
             client: ReqwestClient::new(),
+            ownership_cache: RwLock::new(HashMap::new()),
         }
     }
 
+    /// Returns every token ownership on `account_address`, serving a cached result
+    /// if one was fetched within `TOKEN_OWNERSHIP_CACHE_TTL`. Pages through the
+    /// indexer query in full on a cache miss, since `current_token_ownerships` is
+    /// paginated and a holder can own more than one page's worth of tokens.
     pub async fn get_tokens_on_account(
         &self,
         account_address: &AccountAddress,
-    ) -> Result<tokens_owned_by_account::ResponseData> {
-        // TODO: Do pagination.
-        let variables: tokens_owned_by_account::Variables = tokens_owned_by_account::Variables {
-            owner_address: Some(account_address.to_hex_literal()),
-            offset: Some(0),
-        };
-        let request_body = TokensOwnedByAccount::build_query(variables);
-        let res = self
-            .client
-            .post(self.url.clone())
-            .json(&request_body)
-            .send()
-            .await?;
-        let response: Response<tokens_owned_by_account::ResponseData> = res.json().await?;
-        if let Some(errors) = response.errors {
-            return Err(anyhow::anyhow!(
-                "Errors fetching tokens on account: {:?}",
-                errors
-            ));
+    ) -> Result<Arc<Vec<tokens_owned_by_account::TokensOwnedByAccountCurrentTokenOwnerships>>> {
+        if let Some(cached) = self.ownership_cache.read().await.get(account_address) {
+            if cached.fetched_at.elapsed() < TOKEN_OWNERSHIP_CACHE_TTL {
+                return Ok(cached.ownerships.clone());
+            }
         }
-        match response.data {
-            Some(data) => Ok(data),
-            None => Err(anyhow::anyhow!(
-                "No data returned from indexer when fetching tokens on account"
-            )),
+
+        let mut ownerships = Vec::new();
+        let mut offset: i64 = 0;
+        loop {
+            let variables: tokens_owned_by_account::Variables = tokens_owned_by_account::Variables {
+                owner_address: Some(account_address.to_hex_literal()),
+                offset: Some(offset),
+            };
+            let request_body = TokensOwnedByAccount::build_query(variables);
+            let res = self
+                .client
+                .post(self.url.clone())
+                .json(&request_body)
+                .send()
+                .await?;
+            let response: Response<tokens_owned_by_account::ResponseData> = res.json().await?;
+            if let Some(errors) = response.errors {
+                return Err(anyhow::anyhow!(
+                    "Errors fetching tokens on account: {:?}",
+                    errors
+                ));
+            }
+            let mut page = match response.data {
+                Some(data) => data.current_token_ownerships,
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "No data returned from indexer when fetching tokens on account"
+                    ))
+                }
+            };
+
+            let got_full_page = page.len() == TOKEN_OWNERSHIP_PAGE_SIZE as usize;
+            ownerships.append(&mut page);
+            if !got_full_page {
+                break;
+            }
+            offset += TOKEN_OWNERSHIP_PAGE_SIZE;
         }
+
+        let ownerships = Arc::new(ownerships);
+        self.ownership_cache.write().await.insert(
+            *account_address,
+            CachedOwnership {
+                ownerships: ownerships.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(ownerships)
+    }
+
+    /// Forces the next `get_tokens_on_account` call for `account_address` to bypass
+    /// the cache and hit the indexer again, for when a cached ownership result may be
+    /// stale. Nothing in this crate calls it.
+    pub async fn invalidate_cached_ownership(&self, account_address: &AccountAddress) {
+        self.ownership_cache.write().await.remove(account_address);
     }
 }
 
@@ -0,0 +1,417 @@
+use crate::api::JoinChatRoomRequest;
+use crate::auth::authenticate_user;
+use crate::hub::{Hub, HubOptions, HubPersistence};
+use crate::indexer::IndexerClient;
+use crate::proto::{Input, InputParcel, JoinInput, Output, PostInput};
+use crate::storage::Storage;
+use crate::types::HubId;
+use aptos_logger::{error, info};
+use aptos_sdk::types::account_address::AccountAddress;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::sync::RwLock;
+
+/// A second, additive frontend onto the same `Hub`/`Feed`/auth subsystem the
+/// websocket `Server` uses, so standard IRC clients can connect to token-gated
+/// rooms. A room is addressed as `#<collection>@<creator>`, the gate is SASL PLAIN
+/// carrying the same signed-message proof the websocket route takes as query params,
+/// and everything past that point (the `Hub`, persistence, auth) is shared, not
+/// reimplemented.
+///
+/// Capability negotiation answers `CAP LS` only, and SASL is a single
+/// `AUTHENTICATE <base64 payload>` line: `CAP REQ`/`CAP ACK` and the
+/// `AUTHENTICATE PLAIN` / `AUTHENTICATE +` exchange are not implemented.
+pub struct IrcGateway {
+    hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+    indexer_client: Arc<IndexerClient>,
+    storage: Arc<Storage>,
+}
+
+impl IrcGateway {
+    pub fn new(
+        hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+        indexer_client: Arc<IndexerClient>,
+        storage: Arc<Storage>,
+    ) -> Self {
+        IrcGateway {
+            hubs,
+            indexer_client,
+            storage,
+        }
+    }
+
+    pub async fn run(&self, listen_address: &str, listen_port: u16) -> anyhow::Result<()> {
+        let listener = TcpListener::bind((listen_address, listen_port)).await?;
+        info!(address = listen_address, port = listen_port, event = "irc_listening");
+        loop {
+            let (socket, peer_address) = listener.accept().await?;
+            let hubs = self.hubs.clone();
+            let indexer_client = self.indexer_client.clone();
+            let storage = self.storage.clone();
+            tokio::spawn(async move {
+                if let Err(err) =
+                    Self::handle_connection(socket, hubs, indexer_client, storage).await
+                {
+                    error!(error = ?err, peer = ?peer_address, event = "irc_connection_error");
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(
+        socket: TcpStream,
+        hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+        indexer_client: Arc<IndexerClient>,
+        storage: Arc<Storage>,
+    ) -> anyhow::Result<()> {
+        let (read_half, mut write_half) = socket.into_split();
+        let mut lines = BufReader::new(read_half).lines();
+
+        let mut nickname = String::new();
+        // Set once SASL PLAIN verifies account ownership + token gating for a room.
+        let mut session: Option<Session> = None;
+
+        while let Some(line) = lines.next_line().await? {
+            let command = IrcCommand::parse(&line);
+            match command {
+                Some(IrcCommand::Nick(nick)) => nickname = nick,
+                Some(IrcCommand::User) => {
+                    // USER is otherwise unused here: identity comes from SASL, not
+                    // from the unauthenticated USER command's claimed username.
+                }
+                Some(IrcCommand::CapLs) => {
+                    write_half.write_all(b":server CAP * LS :sasl\r\n").await?;
+                }
+                Some(IrcCommand::AuthenticatePlain(payload)) => {
+                    match Self::authenticate_sasl_plain(&indexer_client, &payload).await {
+                        Ok((hub_id, chat_room_creator, chat_room_name, address)) => {
+                            write_half
+                                .write_all(format!(":server 900 {} :You are now logged in\r\n", nickname).as_bytes())
+                                .await?;
+                            write_half
+                                .write_all(format!(":server 903 {} :SASL authentication successful\r\n", nickname).as_bytes())
+                                .await?;
+                            session = Some(Session {
+                                address,
+                                hub_id,
+                                chat_room_creator,
+                                chat_room_name,
+                                channel: None,
+                            });
+                        }
+                        Err(err) => {
+                            write_half
+                                .write_all(format!(":server 904 {} :SASL authentication failed: {}\r\n", nickname, err).as_bytes())
+                                .await?;
+                        }
+                    }
+                }
+                Some(IrcCommand::Join(channel)) => {
+                    let Some(session) = session.as_mut() else {
+                        write_half
+                            .write_all(b":server 451 JOIN :You have not authenticated via SASL\r\n")
+                            .await?;
+                        continue;
+                    };
+                    // The channel must name the same room the SASL payload authenticated for.
+                    let addressed_hub_id = channel
+                        .strip_prefix('#')
+                        .and_then(|name| name.rsplit_once('@'))
+                        .and_then(|(collection, creator)| {
+                            let creator = AccountAddress::from_str(creator).ok()?;
+                            Some(HubId::new(creator, collection.to_string()))
+                        });
+                    if addressed_hub_id.as_ref() != Some(&session.hub_id) {
+                        write_half
+                            .write_all(format!(":server 403 {} {} :No such channel\r\n", nickname, channel).as_bytes())
+                            .await?;
+                        continue;
+                    }
+                    session.channel = Some(channel.clone());
+                    Self::run_room(
+                        &hubs,
+                        &storage,
+                        session,
+                        &nickname,
+                        &channel,
+                        &mut write_half,
+                        &mut lines,
+                    )
+                    .await?;
+                    // `run_room` only returns once the client PARTs or disconnects.
+                    return Ok(());
+                }
+                Some(IrcCommand::Ping(token)) => {
+                    write_half
+                        .write_all(format!(":server PONG :{}\r\n", token).as_bytes())
+                        .await?;
+                }
+                // WHO only makes sense once a channel has been joined; see the
+                // handler in `run_room`.
+                Some(IrcCommand::Who(_)) | Some(IrcCommand::Quit) | None => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the SASL PLAIN payload as `authzid\0authcid\0password`, treating the
+    /// "password" slot as `<creator>/<collection>/<signature>/<full_message>`, which
+    /// the IRC client's SASL step packs the websocket route's query params into.
+    /// Verifies it the same way `authenticate_user` verifies a websocket join.
+    async fn authenticate_sasl_plain(
+        indexer_client: &Arc<IndexerClient>,
+        payload: &str,
+    ) -> anyhow::Result<(HubId, AccountAddress, String, AccountAddress)> {
+        let decoded = base64::decode(payload.trim())?;
+        let decoded = String::from_utf8(decoded)?;
+        let mut parts = decoded.splitn(3, '\0');
+        let _authzid = parts.next().unwrap_or_default();
+        let chat_room_joiner = parts.next().unwrap_or_default().to_string();
+        let password = parts.next().unwrap_or_default();
+
+        let mut password_parts = password.splitn(4, '/');
+        let chat_room_creator = password_parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Missing chat_room_creator in SASL payload"))?;
+        let chat_room_name = password_parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Missing chat_room_name in SASL payload"))?
+            .to_string();
+        let signature = password_parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Missing signature in SASL payload"))?
+            .to_string();
+        let full_message = password_parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Missing full_message in SASL payload"))?
+            .to_string();
+
+        let chat_room_creator = AccountAddress::from_str(chat_room_creator)?;
+        let request = JoinChatRoomRequest {
+            chat_room_creator,
+            chat_room_name: chat_room_name.clone(),
+            chat_room_joiner,
+            signature,
+            full_message,
+        };
+        let address = authenticate_user(indexer_client.clone(), &request)
+            .await
+            .map_err(|err| anyhow::anyhow!("{}", err))?;
+        let hub_id = HubId::new(chat_room_creator, chat_room_name.clone());
+        Ok((hub_id, chat_room_creator, chat_room_name, address))
+    }
+
+    /// Finds or creates the `Hub` for `session`, joins it, and relays
+    /// `InputParcel`/`OutputParcel` traffic as IRC PRIVMSG/JOIN/PART lines until the
+    /// client disconnects. This is the IRC-shaped mirror of
+    /// `Server::process_client`'s websocket loop.
+    async fn run_room(
+        hubs: &Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+        storage: &Arc<Storage>,
+        session: &Session,
+        nickname: &str,
+        channel: &str,
+        write_half: &mut tokio::net::tcp::OwnedWriteHalf,
+        lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+    ) -> anyhow::Result<()> {
+        let hub = {
+            let mut hubs = hubs.write().await;
+            if let Some(hub) = hubs.get(&session.hub_id) {
+                hub.clone()
+            } else {
+                let initial_feed = storage
+                    .load_feed(&session.chat_room_creator, &session.chat_room_name)
+                    .await
+                    .unwrap_or_default();
+                let hub = Arc::new(Hub::new(HubOptions {
+                    alive_interval: Some(Duration::from_secs(5)),
+                    persistence: Some(HubPersistence {
+                        storage: storage.clone(),
+                        creator_address: session.chat_room_creator,
+                        collection_name: session.chat_room_name.clone(),
+                        initial_feed,
+                    }),
+                }));
+                hubs.insert(session.hub_id.clone(), hub.clone());
+                hub
+            }
+        };
+
+        let (input_sender, input_receiver) = mpsc::unbounded_channel::<InputParcel>();
+        let mut output_receiver = hub.subscribe();
+
+        input_sender
+            .send(InputParcel::new(
+                session.address,
+                Input::Join(JoinInput {
+                    address: session.address,
+                }),
+            ))
+            .ok();
+
+        write_half
+            .write_all(format!(":{} JOIN :{}\r\n", nickname, channel).as_bytes())
+            .await?;
+
+        let running_hub = hub.run(input_receiver);
+        tokio::pin!(running_hub);
+
+        loop {
+            tokio::select! {
+                biased;
+
+                line = lines.next_line() => {
+                    let Some(line) = line? else { break };
+                    match IrcCommand::parse(&line) {
+                        Some(IrcCommand::Privmsg(_target, body)) => {
+                            input_sender
+                                .send(InputParcel::new(session.address, Input::Post(PostInput { body })))
+                                .ok();
+                        }
+                        Some(IrcCommand::Part(_)) | Some(IrcCommand::Quit) => break,
+                        Some(IrcCommand::Who(_target)) => {
+                            for address in hub.users().await {
+                                write_half
+                                    .write_all(
+                                        format!(
+                                            ":server 352 {} {} * * server {} H :0 {}\r\n",
+                                            nickname, channel, address, address
+                                        )
+                                        .as_bytes(),
+                                    )
+                                    .await?;
+                            }
+                            write_half
+                                .write_all(
+                                    format!(":server 315 {} {} :End of WHO list\r\n", nickname, channel)
+                                        .as_bytes(),
+                                )
+                                .await?;
+                        }
+                        Some(IrcCommand::Ping(token)) => {
+                            write_half.write_all(format!(":server PONG :{}\r\n", token).as_bytes()).await?;
+                        }
+                        _ => {}
+                    }
+                }
+
+                output_parcel = output_receiver.recv() => {
+                    let Ok(output_parcel) = output_parcel else { continue };
+                    if output_parcel.client_address != session.address {
+                        continue;
+                    }
+                    if let Some(line) = render_output(&output_parcel.output, channel) {
+                        write_half.write_all(line.as_bytes()).await?;
+                    }
+                }
+
+                _ = &mut running_hub => break,
+            }
+        }
+
+        hub.on_disconnect(session.address).await;
+        write_half
+            .write_all(format!(":{} PART {}\r\n", nickname, channel).as_bytes())
+            .await?;
+        Ok(())
+    }
+}
+
+struct Session {
+    address: AccountAddress,
+    hub_id: HubId,
+    chat_room_creator: AccountAddress,
+    chat_room_name: String,
+    channel: Option<String>,
+}
+
+enum IrcCommand {
+    Nick(String),
+    User,
+    CapLs,
+    AuthenticatePlain(String),
+    Join(String),
+    Part(String),
+    Privmsg(String, String),
+    Who(String),
+    Ping(String),
+    Quit,
+}
+
+impl IrcCommand {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let mut parts = line.splitn(2, ' ');
+        let verb = parts.next()?.to_uppercase();
+        let rest = parts.next().unwrap_or_default();
+        match verb.as_str() {
+            "NICK" => Some(IrcCommand::Nick(rest.trim().to_string())),
+            "USER" => Some(IrcCommand::User),
+            "CAP" if rest.to_uppercase().starts_with("LS") => Some(IrcCommand::CapLs),
+            "AUTHENTICATE" => Some(IrcCommand::AuthenticatePlain(rest.trim().to_string())),
+            "JOIN" => Some(IrcCommand::Join(rest.trim().to_string())),
+            "PART" => Some(IrcCommand::Part(rest.trim().to_string())),
+            "PRIVMSG" => {
+                let mut privmsg_parts = rest.splitn(2, " :");
+                let target = privmsg_parts.next().unwrap_or_default().trim().to_string();
+                let body = privmsg_parts.next().unwrap_or_default().to_string();
+                Some(IrcCommand::Privmsg(target, body))
+            }
+            "WHO" => Some(IrcCommand::Who(rest.trim().to_string())),
+            "PING" => Some(IrcCommand::Ping(rest.trim_start_matches(':').to_string())),
+            "QUIT" => Some(IrcCommand::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Strips CR/LF from a message body before it's interpolated into a raw IRC line.
+/// `body` reaches here as free-form text from a websocket client (see `PostInput`),
+/// and every `render_output` line is terminated with its own `\r\n`; leaving a
+/// client-supplied `\r` or `\n` in would let it inject arbitrary forged IRC lines
+/// (e.g. a spoofed PRIVMSG/JOIN from any nick) into every other client in the channel.
+fn sanitize_irc_line(body: &str) -> String {
+    body.replace(['\r', '\n'], "")
+}
+
+/// Maps a `Hub` broadcast `Output` onto the IRC numerics/commands a client expects.
+fn render_output(output: &Output, channel: &str) -> Option<String> {
+    match output {
+        Output::Joined(joined) => Some(format!(
+            ":server 353 {} = {} :{}\r\n",
+            joined.user.address,
+            channel,
+            joined
+                .users
+                .iter()
+                .map(|user| user.address.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )),
+        Output::UserJoined(event) => Some(format!(":{} JOIN :{}\r\n", event.user.address, channel)),
+        Output::UserLeft(event) => Some(format!(":{} PART {}\r\n", event.address, channel)),
+        Output::Posted(posted) => Some(format!(
+            ":{} PRIVMSG {} :{}\r\n",
+            posted.message.user.address,
+            channel,
+            sanitize_irc_line(&posted.message.body)
+        )),
+        Output::UserPosted(posted) => Some(format!(
+            ":{} PRIVMSG {} :{}\r\n",
+            posted.message.user.address,
+            channel,
+            sanitize_irc_line(&posted.message.body)
+        )),
+        // Dialogs aren't reachable from the IRC gateway, which only ever joins
+        // `#collection@creator` group rooms.
+        // Session tokens are a websocket-only mechanism.
+        Output::SessionIssued(_) | Output::History(_) | Output::DialogOpened(_)
+        | Output::DialogPosted(_) | Output::Error(_) | Output::Alive => None,
+    }
+}
@@ -0,0 +1,16 @@
+pub mod api;
+pub mod args;
+pub mod auth;
+pub mod client;
+pub mod cluster;
+pub mod dialog;
+pub mod error;
+pub mod hub;
+pub mod indexer;
+pub mod irc;
+pub mod model;
+pub mod proto;
+pub mod server;
+pub mod session;
+pub mod storage;
+pub mod types;
@@ -11,6 +11,8 @@ async fn main() {
     let args = RootArgs::parse();
     info!("Running with args: {:#?}", args);
 
-    let server = Server::new(args);
+    let server = Server::new(args)
+        .await
+        .expect("Failed to initialize server");
     server.run().await;
 }
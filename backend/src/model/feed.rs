@@ -0,0 +1,17 @@
+use crate::model::message::Message;
+
+/// The ordered history of messages posted in a single `Hub`, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Feed {
+    messages: Vec<Message>,
+}
+
+impl Feed {
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    pub fn messages_iter(&self) -> impl Iterator<Item = &Message> {
+        self.messages.iter()
+    }
+}
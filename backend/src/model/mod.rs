@@ -0,0 +1,3 @@
+pub mod feed;
+pub mod message;
+pub mod user;
@@ -0,0 +1,289 @@
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A message a client sends to the server over the chat websocket, tagged on the wire
+/// by `type` so new variants can be added without breaking existing clients.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Input {
+    Join(JoinInput),
+    Post(PostInput),
+    History(HistoryInput),
+    OpenDialog(OpenDialogInput),
+    PostDialog(PostDialogInput),
+}
+
+/// An `Input` paired with the server-verified address of the client that sent it, so
+/// a `Hub` never has to trust anything the client claims about its own identity.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InputParcel {
+    pub client_address: AccountAddress,
+    pub input: Input,
+}
+
+impl InputParcel {
+    pub fn new(client_address: AccountAddress, input: Input) -> Self {
+        InputParcel {
+            client_address,
+            input,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JoinInput {
+    pub address: AccountAddress,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PostInput {
+    pub body: String,
+}
+
+/// Opens (or resumes) the dialog between the sender and `peer_address`. Sent once per
+/// connection to a `/dm/<creator>/<collection>/<peer_address>` socket, mirroring
+/// `JoinInput` for the group case.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenDialogInput {
+    pub peer_address: AccountAddress,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PostDialogInput {
+    pub peer_address: AccountAddress,
+    pub body: String,
+}
+
+/// A CHATHISTORY-style bounded query over a hub's message feed. `N` in each
+/// subcommand is clamped server-side to `MAX_HISTORY_LIMIT`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryInput {
+    pub subcommand: HistorySubcommand,
+}
+
+/// A point to query history relative to: a message, identified either by its id or
+/// by its exact `created_at`. Either can be used as a `Before`/`After`/`Between`
+/// bound. A reference that matches no message counts as the end of the feed for
+/// `Before`/`After`, and as the start or end of the feed for `Between`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum HistoryReference {
+    MessageId(Uuid),
+    Timestamp(DateTime<Utc>),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum HistorySubcommand {
+    /// The most recent `limit` messages.
+    Latest { limit: Option<usize> },
+    /// Up to `limit` messages older than `reference`.
+    Before {
+        reference: HistoryReference,
+        limit: Option<usize>,
+    },
+    /// Up to `limit` messages newer than `reference`.
+    After {
+        reference: HistoryReference,
+        limit: Option<usize>,
+    },
+    /// Every message between the two references, inclusive, up to the server max.
+    Between {
+        start: HistoryReference,
+        end: HistoryReference,
+    },
+}
+
+/// A message the server sends to a client over the chat websocket.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Output {
+    SessionIssued(SessionIssuedOutput),
+    Joined(JoinedOutput),
+    UserJoined(UserJoinedOutput),
+    UserLeft(UserLeftOutput),
+    Posted(PostedOutput),
+    UserPosted(UserPostedOutput),
+    History(HistoryOutput),
+    DialogOpened(DialogOpenedOutput),
+    DialogPosted(DialogPostedOutput),
+    Error(OutputError),
+    Alive,
+}
+
+/// An `Output` paired with the address of the client it's meant for. `Hub` broadcasts
+/// every `OutputParcel` to every subscriber; each `Client` filters down to the parcels
+/// addressed to it in `Client::write_output`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OutputParcel {
+    pub client_address: AccountAddress,
+    pub output: Output,
+}
+
+impl OutputParcel {
+    pub fn new(client_address: AccountAddress, output: Output) -> Self {
+        OutputParcel {
+            client_address,
+            output,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UserOutput {
+    pub address: AccountAddress,
+}
+
+impl UserOutput {
+    pub fn new(address: AccountAddress) -> Self {
+        UserOutput { address }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MessageOutput {
+    pub id: Uuid,
+    pub user: UserOutput,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MessageOutput {
+    pub fn new(id: Uuid, user: UserOutput, body: &str, created_at: DateTime<Utc>) -> Self {
+        MessageOutput {
+            id,
+            user,
+            body: body.to_string(),
+            created_at,
+        }
+    }
+}
+
+/// The very first frame sent on a freshly-upgraded `/chat` socket, carrying the
+/// resumable session token a reconnecting client can present as `session_token` on
+/// its next `/chat` query to skip `authenticate_user`. Sent as a frame rather than a
+/// response header since the browser `WebSocket` API this targets has no way to read
+/// response headers on the upgrade.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionIssuedOutput {
+    pub session_token: String,
+}
+
+impl SessionIssuedOutput {
+    pub fn new(session_token: String) -> Self {
+        SessionIssuedOutput { session_token }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JoinedOutput {
+    pub user: UserOutput,
+    pub users: Vec<UserOutput>,
+    pub messages: Vec<MessageOutput>,
+}
+
+impl JoinedOutput {
+    pub fn new(user: UserOutput, users: Vec<UserOutput>, messages: Vec<MessageOutput>) -> Self {
+        JoinedOutput {
+            user,
+            users,
+            messages,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UserJoinedOutput {
+    pub user: UserOutput,
+}
+
+impl UserJoinedOutput {
+    pub fn new(user: UserOutput) -> Self {
+        UserJoinedOutput { user }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UserLeftOutput {
+    pub address: AccountAddress,
+}
+
+impl UserLeftOutput {
+    pub fn new(address: AccountAddress) -> Self {
+        UserLeftOutput { address }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PostedOutput {
+    pub message: MessageOutput,
+}
+
+impl PostedOutput {
+    pub fn new(message: MessageOutput) -> Self {
+        PostedOutput { message }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UserPostedOutput {
+    pub message: MessageOutput,
+}
+
+impl UserPostedOutput {
+    pub fn new(message: MessageOutput) -> Self {
+        UserPostedOutput { message }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum OutputError {
+    NotJoined,
+    InvalidMessageBody,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryOutput {
+    pub messages: Vec<MessageOutput>,
+    /// Whether more messages exist past the bound of this response, i.e. the query
+    /// hit `MAX_HISTORY_LIMIT` or the room has more history than `limit` requested.
+    pub has_more: bool,
+}
+
+impl HistoryOutput {
+    pub fn new(messages: Vec<MessageOutput>, has_more: bool) -> Self {
+        HistoryOutput { messages, has_more }
+    }
+}
+
+/// Sent back to the opener once a dialog is ready, carrying its history so far, same
+/// as `JoinedOutput` does for the group case.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DialogOpenedOutput {
+    pub peer: UserOutput,
+    pub messages: Vec<MessageOutput>,
+}
+
+impl DialogOpenedOutput {
+    pub fn new(peer: UserOutput, messages: Vec<MessageOutput>) -> Self {
+        DialogOpenedOutput { peer, messages }
+    }
+}
+
+/// A message posted into a dialog, sent to both participants.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DialogPostedOutput {
+    pub peer_address: AccountAddress,
+    pub message: MessageOutput,
+}
+
+impl DialogPostedOutput {
+    pub fn new(peer_address: AccountAddress, message: MessageOutput) -> Self {
+        DialogPostedOutput {
+            peer_address,
+            message,
+        }
+    }
+}
@@ -1,14 +1,23 @@
-use crate::api::{handle_rejection, JoinChatRoomRequest};
+use crate::api::{handle_rejection, ApiError, JoinChatRoomQuery};
 use crate::args::RootArgs;
-use crate::auth::authenticate_user;
+use crate::auth::{authenticate_user, authenticate_user_with_session, ensure_holds_token};
 use crate::client::Client;
+use crate::cluster::{Broadcasting, ClusterClient, ClusterMetadata, NodeId};
+use crate::dialog::DialogRegistry;
 use crate::hub::{Hub, HubOptions};
+use crate::hub::HubPersistence;
 use crate::indexer::IndexerClient;
-use crate::proto::InputParcel;
+use crate::irc::IrcGateway;
+use crate::proto::{
+    Input, InputParcel, OpenDialogInput, Output, OutputParcel, PostDialogInput, SessionIssuedOutput,
+};
+use crate::session::SessionTokens;
+use crate::storage::Storage;
 use crate::types::HubId;
 use anyhow::Context;
 use aptos_logger::{error, info};
 use aptos_sdk::rest_client::Client as ApiClient;
+use aptos_sdk::types::account_address::AccountAddress;
 use futures::{StreamExt, TryStreamExt};
 use std::collections::HashMap;
 use std::net::IpAddr;
@@ -18,6 +27,7 @@ use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
 use warp::ws::WebSocket;
 use warp::Filter;
 
@@ -27,6 +37,10 @@ pub struct Server {
     listen_address: String,
     listen_port: u16,
 
+    // Where the IRC gateway (see `crate::irc`) listens, if at all.
+    irc_listen_address: String,
+    irc_listen_port: u16,
+
     // Map of HubId to Hub (aka chat room).
     hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
 
@@ -35,50 +49,303 @@ pub struct Server {
 
     // Client for connecting to an Aptos indexer
     indexer_client: Arc<IndexerClient>,
+
+    // Persists feeds, room metadata, and memberships so they survive a restart.
+    storage: Arc<Storage>,
+
+    // Maps a hub to the node that owns it. A single-node deployment (the default)
+    // owns every hub locally.
+    cluster_metadata: Arc<ClusterMetadata>,
+
+    // Used to proxy a client's inputs to the node that owns its hub.
+    cluster_client: Arc<ClusterClient>,
+
+    // Lets this node re-emit outputs an owning node forwarded to it for hubs this
+    // node doesn't own.
+    broadcasting: Arc<Broadcasting>,
+
+    // Private dialogs between two token holders, separate from any `Hub`.
+    dialog_registry: Arc<DialogRegistry>,
+
+    // Issues and verifies the resumable session tokens that let a reconnecting
+    // client skip the signature+indexer flow in `authenticate_user`.
+    session_tokens: Arc<SessionTokens>,
 }
 
 impl Server {
-    pub fn new(args: RootArgs) -> Self {
-        Server {
+    pub async fn new(args: RootArgs) -> anyhow::Result<Self> {
+        let storage = Storage::connect(&args.storage_args.database_url)
+            .await
+            .context("Failed to connect to storage")?;
+        let session_secret = match args.session_args.session_secret {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                aptos_logger::warn!(event = "session_secret_generated");
+                format!("{}{}", Uuid::new_v4(), Uuid::new_v4()).into_bytes()
+            }
+        };
+        let session_tokens = SessionTokens::new(
+            session_secret,
+            Duration::from_secs(args.session_args.session_ttl_secs),
+        );
+        let cluster_metadata = match args.cluster_args.this_node_url {
+            Some(this_node_url) => ClusterMetadata::new(
+                NodeId(this_node_url),
+                args.cluster_args
+                    .cluster_nodes
+                    .into_iter()
+                    .map(NodeId)
+                    .collect(),
+            ),
+            None => ClusterMetadata::standalone(NodeId("local".to_string())),
+        };
+        Ok(Server {
             listen_address: args.server_args.listen_address,
             listen_port: args.server_args.listen_port,
+            irc_listen_address: args.irc_args.irc_listen_address,
+            irc_listen_port: args.irc_args.irc_listen_port,
             hubs: Arc::new(RwLock::new(HashMap::new())),
             api_client: Arc::new(ApiClient::new(args.fullnode_args.fullnode_url.clone())),
             indexer_client: Arc::new(IndexerClient::new(args.indexer_args.indexer_url.clone())),
-        }
+            storage: Arc::new(storage),
+            cluster_metadata: Arc::new(cluster_metadata),
+            cluster_client: Arc::new(ClusterClient::default()),
+            broadcasting: Arc::new(Broadcasting::default()),
+            dialog_registry: Arc::new(DialogRegistry::default()),
+            session_tokens: Arc::new(session_tokens),
+        })
     }
 
     pub async fn run(&self) {
         let hubs = self.hubs.clone();
-        let api_client = self.api_client.clone();
         let indexer_client = self.indexer_client.clone();
+        let storage = self.storage.clone();
+        let cluster_metadata = self.cluster_metadata.clone();
+        let cluster_client = self.cluster_client.clone();
+        let broadcasting = self.broadcasting.clone();
+        let dialog_registry = self.dialog_registry.clone();
+        let dm_indexer_client = self.indexer_client.clone();
+        let session_tokens = self.session_tokens.clone();
 
-        let chat = warp::path!("chat")
-            .and(warp::ws())
-            .and(warp::any().map(move || api_client.clone()))
+        // The IRC gateway is purely additive: it shares the same `hubs` registry,
+        // `IndexerClient`, and `Storage` as the websocket path below, so a room
+        // reached over IRC is the exact same `Hub` a websocket client would join.
+        // Set to port 0 to disable it entirely.
+        if self.irc_listen_port != 0 {
+            let irc_gateway = IrcGateway::new(
+                self.hubs.clone(),
+                self.indexer_client.clone(),
+                self.storage.clone(),
+            );
+            let irc_listen_address = self.irc_listen_address.clone();
+            let irc_listen_port = self.irc_listen_port;
+            tokio::spawn(async move {
+                if let Err(err) = irc_gateway.run(&irc_listen_address, irc_listen_port).await {
+                    error!(error = ?err, event = "irc_gateway_failed");
+                }
+            });
+        }
+
+        // Separate clones for the internal routes below, since the ones above are
+        // moved into the `chat` filter's closures.
+        let internal_hubs = self.hubs.clone();
+        let internal_broadcasting = self.broadcasting.clone();
+
+        let chat = warp::path!("chat" / AccountAddress / String)
+            .and(warp::query::<JoinChatRoomQuery>())
             .and(warp::any().map(move || indexer_client.clone()))
+            .and(warp::any().map(move || session_tokens.clone()))
+            .and_then(
+                move |chat_room_creator: AccountAddress,
+                      chat_room_name: String,
+                      query: JoinChatRoomQuery,
+                      indexer_client: Arc<IndexerClient>,
+                      session_tokens: Arc<SessionTokens>| async move {
+                    let hub_id = HubId::new(chat_room_creator, chat_room_name.clone());
+                    match authenticate_user_with_session(
+                        indexer_client,
+                        session_tokens,
+                        &hub_id,
+                        chat_room_creator,
+                        chat_room_name.clone(),
+                        query,
+                    )
+                    .await
+                    {
+                        Ok((address, session_token)) => {
+                            Ok((hub_id, chat_room_creator, chat_room_name, address, session_token))
+                        }
+                        Err(err) => Err(warp::reject::custom(err)),
+                    }
+                },
+            )
+            .untuple_one()
+            .and(warp::ws())
             .and(warp::any().map(move || hubs.clone()))
+            .and(warp::any().map(move || storage.clone()))
+            .and(warp::any().map(move || cluster_metadata.clone()))
+            .and(warp::any().map(move || cluster_client.clone()))
+            .and(warp::any().map(move || broadcasting.clone()))
             .map(
-                move |ws: warp::ws::Ws,
-                      api_client: Arc<ApiClient>,
-                      indexer_client: Arc<IndexerClient>,
-                      hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>| {
-                    ws.max_frame_size(MAX_FRAME_SIZE)
-                        .on_upgrade(move |web_socket| async move {
+                move |hub_id: HubId,
+                      chat_room_creator: AccountAddress,
+                      chat_room_name: String,
+                      client_address: AccountAddress,
+                      session_token: String,
+                      ws: warp::ws::Ws,
+                      hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+                      storage: Arc<Storage>,
+                      cluster_metadata: Arc<ClusterMetadata>,
+                      cluster_client: Arc<ClusterClient>,
+                      broadcasting: Arc<Broadcasting>| {
+                    ws.max_frame_size(MAX_FRAME_SIZE).on_upgrade(move |web_socket| async move {
+                        // Keep the local fast-path unchanged when this node owns
+                        // the hub; only go through the cluster machinery when it
+                        // doesn't.
+                        if cluster_metadata.owns_locally(&chat_room_creator, &chat_room_name) {
                             tokio::spawn(Self::process_client(
                                 hubs,
-                                api_client,
-                                indexer_client,
+                                hub_id,
+                                client_address,
+                                storage,
+                                cluster_metadata,
+                                cluster_client,
+                                session_token,
+                                web_socket,
+                            ));
+                        } else {
+                            let owner =
+                                cluster_metadata.owning_node(&chat_room_creator, &chat_room_name).clone();
+                            tokio::spawn(Self::process_remote_client(
+                                owner,
+                                cluster_client,
+                                broadcasting,
+                                hub_id,
+                                client_address,
+                                session_token,
+                                web_socket,
+                            ));
+                        }
+                    })
+                },
+            );
+
+        // `/dm/<creator>/<collection>/<peer_address>` opens a private dialog: the
+        // caller proves ownership the same way `chat` does, and the peer has to be a
+        // legitimate member of the same gated room, even though they aren't present
+        // to prove account ownership themselves. Past the gate, no room `Hub` is
+        // involved: the dialog, with its own `Feed`, comes from the `DialogRegistry`.
+        let dm = warp::path!("dm" / AccountAddress / String / AccountAddress)
+            .and(warp::query::<JoinChatRoomQuery>())
+            .and(warp::any().map(move || dm_indexer_client.clone()))
+            .and_then(
+                move |chat_room_creator: AccountAddress,
+                      chat_room_name: String,
+                      peer_address: AccountAddress,
+                      query: JoinChatRoomQuery,
+                      indexer_client: Arc<IndexerClient>| async move {
+                    let request = query
+                        .into_request(chat_room_creator, chat_room_name.clone())
+                        .map_err(warp::reject::custom)?;
+                    let address = authenticate_user(indexer_client.clone(), &request)
+                        .await
+                        .map_err(warp::reject::custom)?;
+                    ensure_holds_token(indexer_client, peer_address, chat_room_creator, &chat_room_name)
+                        .await
+                        .map_err(warp::reject::custom)?;
+                    Ok::<_, warp::Rejection>((address, peer_address))
+                },
+            )
+            .untuple_one()
+            .and(warp::ws())
+            .and(warp::any().map(move || dialog_registry.clone()))
+            .map(
+                move |client_address: AccountAddress,
+                      peer_address: AccountAddress,
+                      ws: warp::ws::Ws,
+                      dialog_registry: Arc<DialogRegistry>| {
+                    ws.max_frame_size(MAX_FRAME_SIZE)
+                        .on_upgrade(move |web_socket| async move {
+                            tokio::spawn(Self::process_dm_client(
+                                dialog_registry,
+                                client_address,
+                                peer_address,
                                 web_socket,
                             ));
                         })
                 },
             );
 
+        let internal_storage = self.storage.clone();
+        let internal_cluster_metadata = self.cluster_metadata.clone();
+        let internal_cluster_client = self.cluster_client.clone();
+        let internal_disconnect_hubs = self.hubs.clone();
+
+        let internal_input = warp::path!("internal" / "hubs" / String / "inputs")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(warp::any().map(move || internal_hubs.clone()))
+            .and(warp::any().map(move || internal_storage.clone()))
+            .and(warp::any().map(move || internal_cluster_metadata.clone()))
+            .and(warp::any().map(move || internal_cluster_client.clone()))
+            .and_then(
+                |hub_key: String,
+                 input_parcel: InputParcel,
+                 hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+                 storage: Arc<Storage>,
+                 cluster_metadata: Arc<ClusterMetadata>,
+                 cluster_client: Arc<ClusterClient>| async move {
+                    if let Some(hub_id) = HubId::from_storage_key(&hub_key) {
+                        let hub =
+                            Self::get_or_create_hub(hubs, storage, cluster_metadata, cluster_client, hub_id)
+                                .await;
+                        hub.process_external(input_parcel).await;
+                    }
+                    Ok::<_, std::convert::Infallible>(warp::reply())
+                },
+            );
+
+        let internal_output = warp::path!("internal" / "hubs" / String / "outputs")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(warp::any().map(move || internal_broadcasting.clone()))
+            .and_then(
+                |hub_key: String, output_parcel: OutputParcel, broadcasting: Arc<Broadcasting>| async move {
+                    if let Some(hub_id) = HubId::from_storage_key(&hub_key) {
+                        broadcasting.publish(&hub_id, output_parcel).await;
+                    }
+                    Ok::<_, std::convert::Infallible>(warp::reply())
+                },
+            );
+
+        let internal_disconnect = warp::path!("internal" / "hubs" / String / "disconnect")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(warp::any().map(move || internal_disconnect_hubs.clone()))
+            .and_then(
+                |hub_key: String, client_address: AccountAddress, hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>| async move {
+                    if let Some(hub_id) = HubId::from_storage_key(&hub_key) {
+                        // No need to create the hub if it doesn't exist: a hub that was
+                        // never created locally never had this client in its `users`
+                        // map either.
+                        if let Some(hub) = hubs.read().await.get(&hub_id) {
+                            hub.on_disconnect(client_address).await;
+                        }
+                    }
+                    Ok::<_, std::convert::Infallible>(warp::reply())
+                },
+            );
+
         let health = warp::path::end()
             .map(|| "Healthy! Try connecting to a chat room at the /chat endpoint!");
 
-        let routes = chat.or(health).recover(handle_rejection);
+        let routes = chat
+            .or(dm)
+            .or(internal_input)
+            .or(internal_output)
+            .or(internal_disconnect)
+            .or(health)
+            .recover(handle_rejection);
 
         let shutdown = async {
             tokio::signal::ctrl_c()
@@ -98,85 +365,124 @@ impl Server {
         serving.await
     }
 
-    async fn process_client(
+    /// Looks a `Hub` up by `hub_id`, creating (and hydrating it from storage) if this
+    /// is the first time it's been seen on this node. Shared by `process_client`, for
+    /// a client connecting directly to the owning node, and the `internal_input`
+    /// route, for an input a peer forwarded on behalf of a client connected to *it* —
+    /// both need the exact same get-or-create/hydrate/persist behavior.
+    async fn get_or_create_hub(
         hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
-        _api_client: Arc<ApiClient>,
-        indexer_client: Arc<IndexerClient>,
-        mut web_socket: WebSocket,
-    ) {
-        // Here we authenticate the user. It'd be more ideal to do this when the request // is first received, and therefore before we get to this point with an established,
-        // websocket, but unfortunately it's not easy to do this when the request is first
-        // received: https://websockets.readthedocs.io/en/stable/topics/authentication.html,
-        // so instead we block here waiting for the first message, which must contain
-        // the auth info, before proceeding.
-
-        // Wait for the first message.
-        let first_message = match web_socket.try_next().await {
-            Ok(Some(message)) => message,
-            Ok(None) => {
-                error!(event = "disconnected_before_first_message");
-                let _ = web_socket.close().await;
-                return;
-            }
-            Err(e) => {
-                error!(error = ?e, event="error_receiving_first_message");
-                return;
+        storage: Arc<Storage>,
+        cluster_metadata: Arc<ClusterMetadata>,
+        cluster_client: Arc<ClusterClient>,
+        hub_id: HubId,
+    ) -> Arc<Hub> {
+        let mut hubs_guard = hubs.write().await;
+        if let Some(hub) = hubs_guard.get(&hub_id) {
+            return hub.clone();
+        }
+
+        let creator_address = hub_id.creator_address();
+        let collection_name = hub_id.collection_name().to_string();
+
+        let initial_feed = match storage.load_feed(&creator_address, &collection_name).await {
+            Ok(feed) => feed,
+            Err(err) => {
+                error!(error = ?err, event = "load_feed_failed");
+                Default::default()
             }
         };
+        // Write-through the room's metadata row (currently just its `created_at`) the
+        // first time it's seen, so it survives a restart even before anyone has
+        // posted a message.
+        if let Err(err) = storage.ensure_room(&creator_address, &collection_name).await {
+            error!(error = ?err, event = "ensure_room_failed");
+        }
+        let hub = Arc::new(Hub::new(HubOptions {
+            alive_interval: Some(Duration::from_secs(5)),
+            persistence: Some(HubPersistence {
+                storage,
+                creator_address,
+                collection_name,
+                initial_feed,
+            }),
+        }));
+        hubs_guard.insert(hub_id.clone(), hub.clone());
+        drop(hubs_guard);
 
-        // Assert that we can deserialize the first message as a JoinChatRoomRequest.
-        if !first_message.is_text() {
-            error!(event = "first_message_not_text");
-            let _ = web_socket.close().await;
-            return;
+        // This is a config-driven (not gossip-driven) cluster: rather than track which
+        // peers actually have a client proxied for this hub, the owner just forwards
+        // every output to every peer unconditionally. `Broadcasting::publish` is a
+        // no-op on a peer with no subscriber for this hub, so this is cheap when
+        // nobody is proxying and correct when somebody is.
+        let peers = cluster_metadata.peers();
+        if !peers.is_empty() {
+            tokio::spawn(Self::forward_hub_outputs(
+                hub.clone(),
+                hub_id,
+                cluster_client,
+                peers,
+            ));
         }
-        let first_message = match first_message.to_str() {
-            Ok(message) => message,
-            Err(e) => {
-                let _ = web_socket.close().await;
-                error!(error = ?e, event="error_converting_first_message_to_str");
-                return;
-            }
-        };
-        let request: JoinChatRoomRequest = match serde_json::from_str(&first_message) {
-            Ok(message) => message,
-            Err(e) => {
-                let _ = web_socket.close().await;
-                error!(error = ?e, event="error_deserializing_first_message");
-                return;
-            }
-        };
 
-        // Finally, authenticate the request.
-        let user_account_address = match authenticate_user(indexer_client.clone(), &request).await {
-            Ok(address) => address,
-            Err(e) => {
-                let _ = web_socket.close().await;
-                error!(error = ?e, event="user_forbidden");
-                return;
-            }
-        };
+        hub
+    }
 
-        // TODO Use address from request.
-        let client = Client::new(user_account_address);
-
-        let hub_id = HubId::new(request.chat_room_creator, request.chat_room_name);
-
-        // At this point we have verified that the requester is truly the owner of the
-        // account that they say they are. Now we need to check if a Hub already exists
-        // for the chat they're trying to join. If not, we'll create one.
-        let hub = {
-            let mut hubs = hubs.write().await;
-            if let Some(hub) = hubs.get(&hub_id) {
-                hub.clone()
-            } else {
-                let hub = Arc::new(Hub::new(HubOptions {
-                    alive_interval: Some(Duration::from_secs(5)),
-                }));
-                hubs.insert(hub_id.clone(), hub.clone());
-                hub
+    /// Mirrors every `OutputParcel` a locally-owned `Hub` produces out to each peer in
+    /// the cluster, so a client proxied through any of them (see `process_remote_client`)
+    /// still receives live traffic. Spawned once per newly-created hub that has peers.
+    async fn forward_hub_outputs(
+        hub: Arc<Hub>,
+        hub_id: HubId,
+        cluster_client: Arc<ClusterClient>,
+        peers: Vec<NodeId>,
+    ) {
+        let mut outputs = BroadcastStream::new(hub.subscribe());
+        while let Some(output) = outputs.next().await {
+            let output_parcel = match output {
+                Ok(output_parcel) => output_parcel,
+                Err(err) => {
+                    error!(error = ?err, event = "forward_hub_outputs_lagged");
+                    continue;
+                }
+            };
+            for peer in &peers {
+                if let Err(err) = cluster_client.forward_output(peer, &hub_id, &output_parcel).await {
+                    error!(error = ?err, event = "forward_output_failed", peer = peer.0);
+                }
             }
-        };
+        }
+    }
+
+    /// Renders the `Output::SessionIssued` frame sent as the very first message on a
+    /// freshly-upgraded socket, local or proxied (see `process_client` and
+    /// `process_remote_client`).
+    fn session_issued_message(session_token: String) -> Result<warp::ws::Message, warp::Error> {
+        let output = Output::SessionIssued(SessionIssuedOutput::new(session_token));
+        Ok(warp::ws::Message::text(
+            serde_json::to_string(&output).expect("Output always serializes"),
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn process_client(
+        hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+        hub_id: HubId,
+        client_address: AccountAddress,
+        storage: Arc<Storage>,
+        cluster_metadata: Arc<ClusterMetadata>,
+        cluster_client: Arc<ClusterClient>,
+        session_token: String,
+        web_socket: WebSocket,
+    ) {
+        // By this point the `chat` route has already run `authenticate_user_with_session`
+        // against the query params (a session token, or the full `authenticate_user`
+        // flow) and rejected the upgrade on failure, so `client_address` is
+        // a server-verified identity, not something the client gets to assert after
+        // the fact. We no longer wait on a first message to carry the join request.
+        let client = Client::new(client_address);
+
+        let hub = Self::get_or_create_hub(hubs, storage, cluster_metadata, cluster_client, hub_id).await;
 
         let (ws_sink, ws_stream) = web_socket.split();
 
@@ -197,6 +503,10 @@ impl Server {
         let (tx, rx) = mpsc::unbounded_channel();
         let rx = UnboundedReceiverStream::new(rx);
         tokio::spawn(rx.forward(ws_sink));
+        // Sent ahead of anything from the hub, and over the socket rather than as a
+        // response header, since the browser `WebSocket` API this targets has no way
+        // to read headers on the upgrade response.
+        tx.send(Self::session_issued_message(session_token)).unwrap();
         let writing = client
             .write_output(output_receiver)
             .try_for_each(|message| async {
@@ -217,4 +527,140 @@ impl Server {
         hub.on_disconnect(client.address).await;
         info!(address = client.address, event = "disconnected");
     }
+
+    /// The non-owning-node counterpart to `process_client`. Instead of running a
+    /// local `Hub`, it proxies every input the client sends to the owning node over
+    /// HTTP and re-emits whatever outputs that node forwards back to this one.
+    async fn process_remote_client(
+        owner: NodeId,
+        cluster_client: Arc<ClusterClient>,
+        broadcasting: Arc<Broadcasting>,
+        hub_id: HubId,
+        client_address: AccountAddress,
+        session_token: String,
+        web_socket: WebSocket,
+    ) {
+        let client = Client::new(client_address);
+        let (ws_sink, ws_stream) = web_socket.split();
+
+        let output_receiver = broadcasting.subscribe(&hub_id).await;
+        let output_receiver = BroadcastStream::new(output_receiver);
+
+        info!(address = client.address, event = "connected_remote", owner = owner.0);
+
+        let reading = client.read_input(ws_stream).try_for_each(|input_parcel| {
+            let owner = owner.clone();
+            let cluster_client = cluster_client.clone();
+            let hub_id = hub_id.clone();
+            async move {
+                if let Err(err) = cluster_client
+                    .forward_input(&owner, &hub_id, &input_parcel)
+                    .await
+                {
+                    error!(error = ?err, event = "forward_input_failed");
+                }
+                Ok(())
+            }
+        });
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let rx = UnboundedReceiverStream::new(rx);
+        tokio::spawn(rx.forward(ws_sink));
+        tx.send(Self::session_issued_message(session_token)).unwrap();
+        let writing = client
+            .write_output(output_receiver)
+            .try_for_each(|message| async {
+                tx.send(Ok(message)).unwrap();
+                Ok(())
+            });
+
+        if let Err(err) = tokio::select! {
+            result = reading => result.context("Error reading from websocket"),
+            result = writing => result.context("Error writing to websocket"),
+        } {
+            error!("Remote client connection error: {:#}", err);
+        }
+
+        // Mirrors the `hub.on_disconnect` call `process_client` makes for a local
+        // connection: without this, the owner never learns this socket closed and
+        // keeps the client in its `Hub`'s `users` map (and keeps forwarding it
+        // outputs) forever.
+        if let Err(err) = cluster_client
+            .forward_disconnect(&owner, &hub_id, client_address)
+            .await
+        {
+            error!(error = ?err, event = "forward_disconnect_failed");
+        }
+
+        info!(address = client.address, event = "disconnected_remote");
+    }
+
+    /// The dialog counterpart to `process_client`: instead of a `Hub` shared by
+    /// everyone in a room, this drives the single `Dialog` between `client_address`
+    /// and `peer_address`, opening it automatically on connect without waiting for an
+    /// `OpenDialog` message from the client.
+    async fn process_dm_client(
+        dialog_registry: Arc<DialogRegistry>,
+        client_address: AccountAddress,
+        peer_address: AccountAddress,
+        web_socket: WebSocket,
+    ) {
+        let client = Client::new(client_address);
+        let dialog = dialog_registry.get_or_create(client_address, peer_address).await;
+
+        let (ws_sink, ws_stream) = web_socket.split();
+
+        let (input_sender, input_receiver) = mpsc::unbounded_channel::<InputParcel>();
+
+        let output_receiver = dialog.subscribe();
+        let output_receiver = BroadcastStream::new(output_receiver);
+
+        info!(address = client.address, peer = peer_address, event = "dialog_connected");
+
+        input_sender
+            .send(InputParcel::new(
+                client_address,
+                Input::OpenDialog(OpenDialogInput { peer_address }),
+            ))
+            .unwrap();
+
+        let reading = client
+            .read_input(ws_stream)
+            .try_for_each(|input_parcel| async {
+                // The peer address is already fixed by the route; normalize anything
+                // the client sends so it can't address a post at a different dialog
+                // over this socket.
+                let input_parcel = match input_parcel.input {
+                    Input::PostDialog(PostDialogInput { body, .. }) => InputParcel::new(
+                        input_parcel.client_address,
+                        Input::PostDialog(PostDialogInput { peer_address, body }),
+                    ),
+                    input => InputParcel::new(input_parcel.client_address, input),
+                };
+                input_sender.send(input_parcel).unwrap();
+                Ok(())
+            });
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let rx = UnboundedReceiverStream::new(rx);
+        tokio::spawn(rx.forward(ws_sink));
+        let writing = client
+            .write_output(output_receiver)
+            .try_for_each(|message| async {
+                tx.send(Ok(message)).unwrap();
+                Ok(())
+            });
+
+        let running_dialog = dialog.run(input_receiver);
+
+        if let Err(err) = tokio::select! {
+            result = reading => result.context("Error reading from websocket"),
+            result = writing => result.context("Error writing to websocket"),
+            _ = running_dialog => Ok(()),
+        } {
+            error!("Dialog connection error: {:#}", err);
+        }
+
+        info!(address = client.address, peer = peer_address, event = "dialog_disconnected");
+    }
 }
@@ -0,0 +1,101 @@
+use crate::types::HubId;
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Session token was malformed")]
+    Malformed,
+
+    #[error("Session token signature did not match")]
+    BadSignature,
+
+    #[error("Session token has expired")]
+    Expired,
+
+    #[error("Session token was issued for a different chat room")]
+    WrongRoom,
+}
+
+/// Issues and verifies short-lived, HMAC-signed session tokens bound to
+/// `(AccountAddress, HubId, expiry)`. A returning client that presents a valid,
+/// unexpired token skips both `verify_arbitrary_msg` and the
+/// `IndexerClient::get_tokens_on_account` round-trip `authenticate_user` otherwise
+/// requires on every single reconnect; once it expires, the client has to fully
+/// re-authenticate to get a new one.
+pub struct SessionTokens {
+    secret: Vec<u8>,
+    ttl: Duration,
+}
+
+impl SessionTokens {
+    pub fn new(secret: Vec<u8>, ttl: Duration) -> Self {
+        SessionTokens { secret, ttl }
+    }
+
+    /// Issues a token for `address` scoped to `hub_id`, valid for `self.ttl` from now.
+    pub fn issue(&self, address: AccountAddress, hub_id: &HubId) -> String {
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.ttl).unwrap_or_else(|_| chrono::Duration::zero());
+        // `hub_id.storage_key()` goes last since it's the only field that can itself
+        // contain `:`; `verify` below relies on that to split the other two off first
+        // and take the rest as the hub ID, unsplit.
+        let payload = format!(
+            "{}:{}:{}",
+            address.to_hex_literal(),
+            expires_at.timestamp(),
+            hub_id.storage_key(),
+        );
+        let signature = hex::encode(self.sign(&payload));
+        format!("{}.{}", hex::encode(payload), signature)
+    }
+
+    /// Verifies `token`'s signature and expiry and confirms it was issued for
+    /// `hub_id`, returning the address it's bound to.
+    pub fn verify(&self, token: &str, hub_id: &HubId) -> Result<AccountAddress, SessionError> {
+        let (payload_hex, signature_hex) = token.split_once('.').ok_or(SessionError::Malformed)?;
+
+        let payload = hex::decode(payload_hex).map_err(|_| SessionError::Malformed)?;
+        let payload = String::from_utf8(payload).map_err(|_| SessionError::Malformed)?;
+
+        // Constant-time comparison: a naive `==`/`!=` on the decoded or hex-encoded
+        // signature leaks timing information proportional to how many leading bytes
+        // match, letting an attacker forge a valid signature byte-by-byte.
+        let signature = hex::decode(signature_hex).map_err(|_| SessionError::BadSignature)?;
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts a key of any size");
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| SessionError::BadSignature)?;
+
+        let mut parts = payload.splitn(3, ':');
+        let address = parts.next().ok_or(SessionError::Malformed)?;
+        let expires_at = parts.next().ok_or(SessionError::Malformed)?;
+        let token_hub_id = parts.next().ok_or(SessionError::Malformed)?;
+
+        let address = AccountAddress::from_str(address).map_err(|_| SessionError::Malformed)?;
+        let token_hub_id = HubId::from_storage_key(token_hub_id).ok_or(SessionError::Malformed)?;
+        if &token_hub_id != hub_id {
+            return Err(SessionError::WrongRoom);
+        }
+
+        let expires_at: i64 = expires_at.parse().map_err(|_| SessionError::Malformed)?;
+        let expires_at = DateTime::<Utc>::from_timestamp(expires_at, 0).ok_or(SessionError::Malformed)?;
+        if Utc::now() >= expires_at {
+            return Err(SessionError::Expired);
+        }
+
+        Ok(address)
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts a key of any size");
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
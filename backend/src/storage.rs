@@ -0,0 +1,247 @@
+use crate::model::feed::Feed;
+use crate::model::message::Message;
+use crate::model::user::User;
+use anyhow::Context;
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::Row;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Persists feeds, room metadata, and memberships to a SQLite database so a `Hub`
+/// doesn't go back to being empty every time the server process restarts.
+///
+/// Rooms and messages are keyed by the same `(creator_address, collection_name)` pair
+/// that `HubId` is built from; we take the two fields directly rather than depending
+/// on `HubId` itself so this module doesn't need to know anything about its internals.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    /// Opens (creating if necessary) the SQLite database at `database_url` and runs
+    /// migrations against it.
+    pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)
+            .context("Failed to parse database URL")?
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .context("Failed to connect to database")?;
+        let storage = Storage { pool };
+        storage.run_migrations().await?;
+        Ok(storage)
+    }
+
+    async fn run_migrations(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS messages (
+                id TEXT PRIMARY KEY,
+                creator_address TEXT NOT NULL,
+                collection_name TEXT NOT NULL,
+                author_address TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create messages table")?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS messages_room_idx
+                ON messages (creator_address, collection_name, created_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create messages index")?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS memberships (
+                creator_address TEXT NOT NULL,
+                collection_name TEXT NOT NULL,
+                member_address TEXT NOT NULL,
+                PRIMARY KEY (creator_address, collection_name, member_address)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create memberships table")?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS rooms (
+                creator_address TEXT NOT NULL,
+                collection_name TEXT NOT NULL,
+                topic TEXT,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (creator_address, collection_name)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create rooms table")?;
+
+        Ok(())
+    }
+
+    /// Write-through room metadata row, inserted the first time a room is seen so its
+    /// `created_at` (and, once something sets it, `topic`) survive a restart even
+    /// before anyone has posted a message. A no-op if the room already has a row.
+    ///
+    /// There's no command wired up yet that lets a client set a room's topic (e.g. an
+    /// IRC `TOPIC`, or a dedicated `Input` variant) — this only persists the room's
+    /// existence and gives `set_room_topic`/`room_topic` somewhere to read and write.
+    pub async fn ensure_room(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO rooms (creator_address, collection_name, topic, created_at) \
+             VALUES (?1, ?2, NULL, ?3)",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .context("Failed to record room metadata")?;
+        Ok(())
+    }
+
+    /// Loads the room's persisted topic, if any has ever been set.
+    pub async fn room_topic(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let row = sqlx::query(
+            "SELECT topic FROM rooms WHERE creator_address = ?1 AND collection_name = ?2",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load room topic")?;
+
+        match row {
+            Some(row) => Ok(row.try_get("topic")?),
+            None => Ok(None),
+        }
+    }
+
+    /// Persists a new topic for the room, creating its metadata row first if needed.
+    pub async fn set_room_topic(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+        topic: &str,
+    ) -> anyhow::Result<()> {
+        self.ensure_room(creator_address, collection_name).await?;
+        sqlx::query(
+            "UPDATE rooms SET topic = ?3 WHERE creator_address = ?1 AND collection_name = ?2",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .bind(topic)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update room topic")?;
+        Ok(())
+    }
+
+    /// Writes a message to storage. Called before broadcasting so a crash between the
+    /// write and the broadcast can never cause a message clients saw to go missing on
+    /// the next restart.
+    pub async fn record_message(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+        message: &Message,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO messages (id, creator_address, collection_name, author_address, body, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .bind(message.id.to_string())
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .bind(message.user.address.to_hex_literal())
+        .bind(&message.body)
+        .bind(message.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .context("Failed to record message")?;
+        Ok(())
+    }
+
+    /// Hydrates a `Feed` with every message previously recorded for this room, in the
+    /// order they were posted.
+    pub async fn load_feed(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+    ) -> anyhow::Result<Feed> {
+        let rows = sqlx::query(
+            "SELECT id, author_address, body, created_at FROM messages \
+             WHERE creator_address = ?1 AND collection_name = ?2 \
+             ORDER BY created_at ASC",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to load feed")?;
+
+        let mut feed = Feed::default();
+        for row in rows {
+            let id: String = row.try_get("id")?;
+            let author_address: String = row.try_get("author_address")?;
+            let body: String = row.try_get("body")?;
+            let created_at: String = row.try_get("created_at")?;
+
+            let id = Uuid::parse_str(&id).context("Failed to parse stored message id")?;
+            let author_address = AccountAddress::from_str(&author_address)
+                .context("Failed to parse stored author address")?;
+            let created_at: DateTime<Utc> = DateTime::parse_from_rfc3339(&created_at)
+                .context("Failed to parse stored created_at")?
+                .with_timezone(&Utc);
+
+            feed.add_message(Message::new(id, User::new(author_address), &body, created_at));
+        }
+
+        Ok(feed)
+    }
+
+    /// Records that `member_address` has joined the room. This is a write-through, not
+    /// a presence tracker: it's an append-only log of everyone who has ever joined so
+    /// that membership survives a restart even though live presence does not.
+    pub async fn record_membership(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+        member_address: &AccountAddress,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO memberships (creator_address, collection_name, member_address) \
+             VALUES (?1, ?2, ?3)",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .bind(member_address.to_hex_literal())
+        .execute(&self.pool)
+        .await
+        .context("Failed to record membership")?;
+        Ok(())
+    }
+}
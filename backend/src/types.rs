@@ -0,0 +1,46 @@
+use aptos_sdk::types::account_address::AccountAddress;
+
+/// A hub ID is really just the unique ID referring to a token collection, which is just
+/// the creator address + the collection name.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct HubId {
+    // Account that created the collection.
+    creator_address: AccountAddress,
+
+    // Name of the collection.
+    collection_name: String,
+}
+
+impl HubId {
+    pub fn new(creator_address: AccountAddress, collection_name: String) -> Self {
+        HubId {
+            creator_address,
+            collection_name,
+        }
+    }
+
+    pub fn creator_address(&self) -> AccountAddress {
+        self.creator_address
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
+
+    /// A stable string form of this ID, `<creator>::<collection>`, used as a path
+    /// segment for inter-node calls and as a storage key. The collection name is
+    /// included as is, without escaping.
+    pub fn storage_key(&self) -> String {
+        format!("{}::{}", self.creator_address.to_hex_literal(), self.collection_name)
+    }
+
+    /// The inverse of [`Self::storage_key`].
+    pub fn from_storage_key(key: &str) -> Option<Self> {
+        let (creator_address, collection_name) = key.split_once("::")?;
+        let creator_address = AccountAddress::from_hex_literal(creator_address).ok()?;
+        Some(HubId {
+            creator_address,
+            collection_name: collection_name.to_string(),
+        })
+    }
+}
@@ -18,10 +18,36 @@ pub struct JoinChatRoomRequest {
     /// This should be a hex string representation of an account ed25519 public key.
     pub chat_room_joiner: String,
 
-    /// Thie payload that the web UI had the wallet sign to prove that the person
+    /// The payload that the web UI had the wallet sign to prove that the person
     /// making the request to join the room actually owns the account corresponding
-    /// to the given public key.
-    pub verification_payload: String, // todo
+    /// to the given public key. When you call window.aptos.signMessage the response
+    /// contains a field called `signature`. This is a hex encoded representation of
+    /// the signed message. That is what this field should be.
+    pub signature: String,
+
+    /// This is similar to the previous field but instead of signature, it's the fullMessage.
+    pub full_message: String,
+}
+
+/// The first clientbound frame sent on a new `/chat` websocket connection, before
+/// anything else. Carries a single-use nonce the client must embed in the
+/// `full_message` it signs for [`JoinChatRoomRequest`], so a captured request can't be
+/// replayed on a new connection.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HandshakeRequest {
+    pub nonce: String,
+}
+
+/// The serverbound reply to [`HandshakeRequest`], the first and only frame the server
+/// expects before a connection has joined a room.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HandshakeResponse {
+    pub join_chat_room_request: JoinChatRoomRequest,
+
+    /// How many prior messages to replay on join, overriding the server's
+    /// `--history-limit` default. Lets a client request a larger backfill window, e.g.
+    /// after being offline for a while.
+    pub history_limit: Option<u32>,
 }
 
 #[derive(Error, Debug)]
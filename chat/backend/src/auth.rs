@@ -4,51 +4,215 @@ use crate::{
 };
 use aptos_sdk::types::account_address::AccountAddress;
 use aptos_sdk::{
-    crypto::{ed25519::Ed25519PublicKey, ValidCryptoMaterialStringExt},
+    crypto::{
+        ed25519::{Ed25519PublicKey, Ed25519Signature},
+        Signature, ValidCryptoMaterialStringExt,
+    },
     rest_client::Client as ApiClient,
     types::transaction::authenticator::AuthenticationKey,
 };
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time;
+use tokio::time::Instant;
 use warp::Filter;
 
+/// Single-use nonces handed out to freshly-opened `/chat` websockets, keyed by the
+/// nonce itself, with the time each was issued so [`authenticate_join_request`] can
+/// reject one that's outstayed [`NONCE_TTL`]. Lives on `Server` and is shared between
+/// every in-flight handshake.
+pub type NonceStore = Arc<RwLock<HashMap<String, Instant>>>;
+
+pub fn new_nonce_store() -> NonceStore {
+    Arc::new(RwLock::new(HashMap::new()))
+}
+
+/// How long a nonce handed out in a `HandshakeRequest` stays valid. A client that
+/// doesn't complete the handshake within this window has to reconnect to get a new one.
+pub const NONCE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often [`spawn_nonce_eviction_task`] sweeps expired nonces out of the store.
+const NONCE_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A nonce is inserted into the store for every opened socket (see `Server::handshake`)
+/// but only ever removed by `authenticate_join_request` consuming it. A client that
+/// disconnects before completing the handshake — or whose response never parses —
+/// leaves its entry behind forever, since `NONCE_TTL` is otherwise only checked at
+/// consume time. This periodically sweeps out anything older than `NONCE_TTL` so
+/// abandoned handshakes don't accumulate.
+pub fn spawn_nonce_eviction_task(nonces: NonceStore) {
+    tokio::spawn(async move {
+        loop {
+            time::sleep(NONCE_SWEEP_INTERVAL).await;
+            nonces
+                .write()
+                .await
+                .retain(|_, issued_at| issued_at.elapsed() < NONCE_TTL);
+        }
+    });
+}
+
+// This function ensures three things:
+// 1. That `full_message` embeds a nonce we actually issued, which hasn't expired or
+//    already been consumed by another request (see `Server::process_client`).
+// 2. That the user submitting the request to join the chat room actually owns that account.
+// 3. That the user has a token on their account that lets them join that chat room.
+pub async fn authenticate_join_request(
+    indexer_client: Arc<IndexerClient>,
+    nonces: NonceStore,
+    expected_chain_id: &str,
+    join_chat_room_request: &JoinChatRoomRequest,
+) -> Result<AccountAddress, ApiErrors> {
+    // Build an account address based on the request.
+    let public_key =
+        Ed25519PublicKey::from_encoded_string(&join_chat_room_request.chat_room_joiner)
+            .map_err(|err| ApiErrors::BadRequest(err.into()))?;
+    let account_address = AuthenticationKey::ed25519(&public_key).derived_address();
+
+    // First confirm that the user actually owns the account they are trying to join as.
+    // On the client side, the user signed an arbitrary message with their private
+    // key, resulting in the signature we check below.
+    let signature_bytes = hex::decode(&join_chat_room_request.signature)
+        .map_err(|err| ApiErrors::BadRequest(err.into()))?;
+    let signature = Ed25519Signature::try_from(signature_bytes.as_slice())
+        .map_err(|err| ApiErrors::BadRequest(err.into()))?;
+    if signature
+        .verify_arbitrary_msg(join_chat_room_request.full_message.as_bytes(), &public_key)
+        .is_err()
+    {
+        return Err(ApiErrors::NotRealAccountOwner(
+            "Signature did not match the account derived from chat_room_joiner".to_string(),
+        ));
+    }
+
+    // The signed bytes alone only prove the signer controlled some key; they don't
+    // prove it was *this* address signing *this* request, for *this* connection.
+    // `full_message` is the
+    // `APTOS\naddress: ...\napplication: ...\nchainId: ...\nmessage: ...\nnonce: ...`
+    // envelope `window.aptos.signMessage` produces, so parse it and check the embedded
+    // address, chain ID, and nonce all match, otherwise a signature harvested from
+    // another dApp, another account, or an earlier connection could be replayed here.
+    let signed_address = extract_full_message_field(&join_chat_room_request.full_message, "address")
+        .ok_or_else(|| {
+            ApiErrors::NotRealAccountOwner("full_message did not contain an address field".to_string())
+        })?;
+    if signed_address != account_address.to_hex_literal() {
+        return Err(ApiErrors::NotRealAccountOwner(
+            "full_message address did not match the account derived from chat_room_joiner"
+                .to_string(),
+        ));
+    }
+
+    let signed_chain_id = extract_full_message_field(&join_chat_room_request.full_message, "chainId")
+        .ok_or_else(|| {
+            ApiErrors::NotRealAccountOwner("full_message did not contain a chainId field".to_string())
+        })?;
+    if signed_chain_id != expected_chain_id {
+        return Err(ApiErrors::NotRealAccountOwner(
+            "full_message was signed for a different chain".to_string(),
+        ));
+    }
+
+    let signed_nonce = extract_full_message_field(&join_chat_room_request.full_message, "nonce")
+        .ok_or_else(|| {
+            ApiErrors::NotRealAccountOwner("full_message did not contain a nonce field".to_string())
+        })?
+        .to_string();
+    // Consuming the nonce here, rather than just checking for its presence, is what
+    // makes a captured `JoinChatRoomRequest` unusable on a second connection: the
+    // nonce it embeds only exists in the store for the one handshake it was issued for.
+    let nonce_issued_at = nonces.write().await.remove(&signed_nonce);
+    match nonce_issued_at {
+        Some(issued_at) if issued_at.elapsed() < NONCE_TTL => {}
+        _ => {
+            return Err(ApiErrors::NotRealAccountOwner(
+                "full_message nonce was missing, already used, or expired".to_string(),
+            ));
+        }
+    }
+
+    // Next confirm that the user has a token on their account that lets them join the chat room.
+    let account_has_token = account_holds_chat_room_token(
+        &indexer_client,
+        &account_address,
+        &join_chat_room_request.chat_room_creator,
+        &join_chat_room_request.chat_room_name,
+    )
+    .await
+    .map_err(|err| ApiErrors::BadRequest(err.into()))?;
+
+    if !account_has_token {
+        return Err(ApiErrors::DoesNotHoldChatRoomToken(
+            "Account does not have a token that lets them access this chat room".to_string(),
+        ));
+    }
+
+    Ok(account_address)
+}
+
+/// Whether `account_address` currently holds a token minted under
+/// `(chat_room_creator, chat_room_name)`. Used both by [`authenticate_join_request`] at
+/// join time and by `Server`'s background reverification task, which calls this
+/// periodically for every connected client to catch one that has since sold or
+/// transferred away their access token.
+pub async fn account_holds_chat_room_token(
+    indexer_client: &IndexerClient,
+    account_address: &AccountAddress,
+    chat_room_creator: &AccountAddress,
+    chat_room_name: &str,
+) -> anyhow::Result<bool> {
+    let tokens_owned_by_account = indexer_client
+        .get_tokens_on_account(account_address)
+        .await?;
+    Ok(tokens_owned_by_account
+        .current_token_ownerships
+        .iter()
+        .any(|collection| {
+            collection.creator_address == chat_room_creator.to_hex_literal()
+                && collection.collection_name == chat_room_name
+        }))
+}
+
 // This function ensures two things:
 // 1. That the user submitting the request to join the chat room actually owns that account.
 // 2. That the user has a token on their account that lets them join that chat room.
 pub async fn ensure_authentication(
     _aptos_api_client: Arc<ApiClient>,
     indexer_client: Arc<IndexerClient>,
+    nonces: NonceStore,
+    expected_chain_id: String,
 ) -> impl Filter<Extract = (AccountAddress,), Error = warp::reject::Rejection> + Clone {
     // Annoyingly I have to compose the same filters I did in server.rs here to
     // give the middleware closure access to the clients.
-    warp::body::json().and(warp::any().map(move || indexer_client.clone())).and_then(|join_chat_room_request: JoinChatRoomRequest, indexer_client: Arc<IndexerClient>| async move {
-        // Build an account address based on the request.
-        let account_address =
-            match Ed25519PublicKey::from_encoded_string(&join_chat_room_request.chat_room_joiner) {
-                Ok(pub_key) => AuthenticationKey::ed25519(&pub_key).derived_address(),
-                Err(err) => {
-                    return Err(warp::reject::custom(ApiErrors::BadRequest(err.into())));
-                }
-            };
-
-        // First confirm that the user actually owns the account they are trying to join as.
-        // todo
-
-        // Next confirm that the user has a token on their account that lets them join the chat room.
-        let tokens_owned_by_account = indexer_client.get_tokens_on_account(&account_address).await
-            .map_err(|err| warp::reject::custom(ApiErrors::BadRequest(err.into())))?;
-        let mut account_has_token = false;
-        for collection in tokens_owned_by_account.current_token_ownerships.into_iter() {
-            if collection.creator_address == join_chat_room_request.chat_room_creator.to_hex_literal() && collection.collection_name == join_chat_room_request.chat_room_name {
-                account_has_token = true;
-            }
-        }
-
-        if !account_has_token {
-            return Err(warp::reject::custom(ApiErrors::DoesNotHoldChatRoomToken(
-                "Account does not have a token that lets them access this chat room".to_string(),
-            )));
-        }
+    warp::body::json()
+        .and(warp::any().map(move || indexer_client.clone()))
+        .and(warp::any().map(move || nonces.clone()))
+        .and(warp::any().map(move || expected_chain_id.clone()))
+        .and_then(
+            |join_chat_room_request: JoinChatRoomRequest,
+             indexer_client: Arc<IndexerClient>,
+             nonces: NonceStore,
+             expected_chain_id: String| async move {
+                authenticate_join_request(
+                    indexer_client,
+                    nonces,
+                    &expected_chain_id,
+                    &join_chat_room_request,
+                )
+                .await
+                .map_err(warp::reject::custom)
+            },
+        )
+}
 
-        Ok(account_address)
-    })
+// Pulls a `key: value` field out of an Aptos wallet `fullMessage` envelope, e.g.
+// finding `"2"` for `extract_full_message_field(msg, "chainId")` given a line reading
+// `chainId: 2`.
+fn extract_full_message_field<'a>(full_message: &'a str, key: &str) -> Option<&'a str> {
+    let prefix = format!("{}: ", key);
+    full_message
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
 }
@@ -0,0 +1,144 @@
+use crate::types::HubId;
+use anyhow::Context;
+use aptos_sdk::types::account_address::AccountAddress;
+use futures::{SinkExt, StreamExt};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use tokio_tungstenite::tungstenite::Message as PeerMessage;
+use warp::ws::Message;
+
+/// A node in the cluster, identified by the base `ws://`/`wss://` URL other nodes use
+/// to reach its `/chat` route.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NodeId(pub String);
+
+/// Read-only mapping of which node in the cluster owns each hub, so a room is always
+/// handled by the same node regardless of which node's `/chat` route a client happens
+/// to hit. Ownership is assigned by hashing the room identity, which keeps the
+/// mapping stable as long as the node list doesn't change; rebalancing on membership
+/// changes is out of scope here, same as the rest of this config-driven (not
+/// gossip-driven) cluster.
+#[derive(Clone, Debug)]
+pub struct ClusterMetadata {
+    this_node: NodeId,
+    nodes: Vec<NodeId>,
+}
+
+impl ClusterMetadata {
+    pub fn new(this_node: NodeId, mut nodes: Vec<NodeId>) -> Self {
+        if !nodes.contains(&this_node) {
+            nodes.push(this_node.clone());
+        }
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        ClusterMetadata { this_node, nodes }
+    }
+
+    /// A single-node "cluster", i.e. clustering is disabled and every hub is local.
+    pub fn standalone(this_node: NodeId) -> Self {
+        ClusterMetadata::new(this_node.clone(), vec![this_node])
+    }
+
+    pub fn owning_node(&self, creator_address: &AccountAddress, collection_name: &str) -> &NodeId {
+        let mut hasher = DefaultHasher::new();
+        creator_address.hash(&mut hasher);
+        collection_name.hash(&mut hasher);
+        let index = (hasher.finish() as usize) % self.nodes.len();
+        &self.nodes[index]
+    }
+
+    pub fn owns_locally(&self, creator_address: &AccountAddress, collection_name: &str) -> bool {
+        self.owning_node(creator_address, collection_name) == &self.this_node
+    }
+}
+
+/// Bridges a client that connected to this node through to the node that actually
+/// owns its hub, so a room can span server instances without clients needing to know
+/// or care which node owns the room they want to join.
+///
+/// Unlike forwarding parsed `InputParcel`/`OutputParcel`s over HTTP, this proxies the
+/// raw WebSocket frames end-to-end: the nonce handshake and signed join request
+/// (see [`crate::server::Server::handshake`]) happen entirely between the client and
+/// the owning node, with this node never seeing anything it would need to
+/// authenticate or interpret.
+#[derive(Clone, Copy, Default)]
+pub struct ClusterClient;
+
+impl ClusterClient {
+    /// Pumps frames in both directions between `client` (the WebSocket this node
+    /// accepted) and a new connection this node opens to `owner`'s `/chat` route for
+    /// `hub_id`. Returns once either side closes or errors.
+    pub async fn bridge(
+        &self,
+        owner: &NodeId,
+        hub_id: &HubId,
+        client: warp::ws::WebSocket,
+    ) -> anyhow::Result<()> {
+        let owner_url = format!(
+            "{}/chat/{}/{}",
+            owner.0,
+            hub_id.creator_address().to_hex_literal(),
+            hub_id.collection_name()
+        );
+        let (owner_stream, _) = tokio_tungstenite::connect_async(&owner_url)
+            .await
+            .with_context(|| format!("Failed to connect to owning node at {}", owner_url))?;
+
+        let (mut owner_sink, mut owner_stream) = owner_stream.split();
+        let (mut client_sink, mut client_stream) = client.split();
+
+        let to_owner = async {
+            while let Some(message) = client_stream.next().await {
+                let message = message.context("Error reading from client")?;
+                if owner_sink.send(to_peer_message(message)).await.is_err() {
+                    break;
+                }
+            }
+            owner_sink.close().await.ok();
+            Ok::<_, anyhow::Error>(())
+        };
+
+        let to_client = async {
+            while let Some(message) = owner_stream.next().await {
+                let message = message.context("Error reading from owning node")?;
+                let Some(message) = to_client_message(message) else {
+                    continue;
+                };
+                if client_sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+            client_sink.close().await.ok();
+            Ok::<_, anyhow::Error>(())
+        };
+
+        tokio::select! {
+            result = to_owner => result,
+            result = to_client => result,
+        }
+    }
+}
+
+fn to_peer_message(message: Message) -> PeerMessage {
+    if message.is_ping() {
+        PeerMessage::Ping(message.into_bytes())
+    } else if message.is_pong() {
+        PeerMessage::Pong(message.into_bytes())
+    } else if message.is_close() {
+        PeerMessage::Close(None)
+    } else if message.is_binary() {
+        PeerMessage::Binary(message.into_bytes())
+    } else {
+        PeerMessage::Text(String::from_utf8_lossy(&message.into_bytes()).into_owned())
+    }
+}
+
+fn to_client_message(message: PeerMessage) -> Option<Message> {
+    match message {
+        PeerMessage::Text(text) => Some(Message::text(text)),
+        PeerMessage::Binary(data) => Some(Message::binary(data)),
+        PeerMessage::Ping(data) => Some(Message::ping(data)),
+        PeerMessage::Pong(data) => Some(Message::pong(data)),
+        PeerMessage::Close(_) => Some(Message::close()),
+        PeerMessage::Frame(_) => None,
+    }
+}
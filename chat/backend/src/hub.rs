@@ -0,0 +1,197 @@
+use crate::metrics::Metrics;
+use crate::proto::{HistoryOutput, Input, InputParcel, Output, OutputParcel, PostInput, PostedOutput};
+use crate::storage::Storage;
+use crate::types::HubId;
+use aptos_logger::error;
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::Utc;
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{broadcast, RwLock};
+use tokio::time;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+const OUTPUT_CHANNEL_SIZE: usize = 16;
+const MAX_MESSAGE_BODY_LENGTH: usize = 256;
+
+/// How many messages a joining client is replayed by default if it doesn't ask for a
+/// specific backfill window. Overridable per-join via
+/// `HandshakeResponse::history_limit`.
+pub const DEFAULT_HISTORY_LIMIT: u32 = 50;
+
+#[derive(Default)]
+pub struct HubOptions {
+    pub alive_interval: Option<Duration>,
+}
+
+// A Hub is a single chat room, keyed by `(creator_address, collection_name)`.
+pub struct Hub {
+    alive_interval: Option<Duration>,
+    creator_address: AccountAddress,
+    collection_name: String,
+    storage: Arc<Storage>,
+    metrics: Arc<Metrics>,
+    output_sender: broadcast::Sender<OutputParcel>,
+    disconnect_sender: broadcast::Sender<AccountAddress>,
+    members: RwLock<HashSet<AccountAddress>>,
+}
+
+impl Hub {
+    pub fn new(
+        hub_id: &HubId,
+        storage: Arc<Storage>,
+        metrics: Arc<Metrics>,
+        options: HubOptions,
+    ) -> Self {
+        let (output_sender, _) = broadcast::channel(OUTPUT_CHANNEL_SIZE);
+        let (disconnect_sender, _) = broadcast::channel(OUTPUT_CHANNEL_SIZE);
+        Hub {
+            alive_interval: options.alive_interval,
+            creator_address: hub_id.creator_address(),
+            collection_name: hub_id.collection_name().to_string(),
+            storage,
+            metrics,
+            output_sender,
+            disconnect_sender,
+            members: RwLock::new(HashSet::new()),
+        }
+    }
+
+    pub fn creator_address(&self) -> AccountAddress {
+        self.creator_address
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
+
+    pub async fn run(&self, receiver: UnboundedReceiver<InputParcel>) {
+        let ticking_alive = self.tick_alive();
+        let receiver = UnboundedReceiverStream::new(receiver);
+        let processing = receiver.for_each(|input_parcel| self.process(input_parcel));
+        tokio::select! {
+            _ = ticking_alive => {},
+            _ = processing => {},
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OutputParcel> {
+        self.output_sender.subscribe()
+    }
+
+    /// Registers `client_address` as a connected member, so future posts (and
+    /// keepalives) get broadcast to it. Called by `Server::process_client` once a
+    /// client's handshake succeeds, just before it subscribes to the hub's output.
+    pub async fn register(&self, client_address: AccountAddress) {
+        self.members.write().await.insert(client_address);
+    }
+
+    pub async fn on_disconnect(&self, client_address: AccountAddress) {
+        self.members.write().await.remove(&client_address);
+    }
+
+    /// Snapshot of currently-registered members, for the background reverification
+    /// task in `Server` to check each of them against the indexer without holding the
+    /// members lock for the duration of those calls.
+    pub async fn members(&self) -> Vec<AccountAddress> {
+        self.members.read().await.iter().copied().collect()
+    }
+
+    /// Subscribes to forced disconnects, so `Server::process_client` can race this
+    /// against its read/write loops and drop the connection the moment this client is
+    /// kicked, rather than only noticing next time it tries to send something.
+    pub fn subscribe_disconnects(&self) -> broadcast::Receiver<AccountAddress> {
+        self.disconnect_sender.subscribe()
+    }
+
+    /// Forces `client_address` off this hub, e.g. because the background
+    /// reverification task found it no longer holds the room's access token.
+    pub async fn kick(&self, client_address: AccountAddress) {
+        self.members.write().await.remove(&client_address);
+        let _ = self.disconnect_sender.send(client_address);
+    }
+
+    /// Loads the last `limit` messages persisted for this room and sends them to
+    /// `client_address` as a single `Output::History`. `Server::process_client` calls
+    /// this right after a client registers, before it's handed the live broadcast
+    /// stream, so a joining client sees what happened before it connected.
+    pub async fn send_history(&self, client_address: AccountAddress, limit: u32) {
+        let messages = match self
+            .storage
+            .load_recent_messages(&self.creator_address, &self.collection_name, limit)
+            .await
+        {
+            Ok(messages) => messages,
+            Err(err) => {
+                error!(error = ?err, event = "load_history_failed");
+                return;
+            }
+        };
+        self.send_targeted(client_address, Output::History(HistoryOutput { messages }));
+    }
+
+    async fn process(&self, input_parcel: InputParcel) {
+        match input_parcel.input {
+            Input::Post(input) => self.process_post(input_parcel.client_address, input).await,
+        }
+    }
+
+    async fn process_post(&self, client_address: AccountAddress, input: PostInput) {
+        if input.body.is_empty() || input.body.len() > MAX_MESSAGE_BODY_LENGTH {
+            return;
+        }
+
+        let message = PostedOutput {
+            address: client_address,
+            body: input.body,
+            created_at: Utc::now(),
+        };
+
+        // Write through to storage before broadcasting, so a crash after the broadcast
+        // can't lose a message clients have already seen. A failed write is only
+        // logged; the message is still broadcast.
+        if let Err(err) = self
+            .storage
+            .record_message(&self.creator_address, &self.collection_name, &message)
+            .await
+        {
+            error!(error = ?err, event = "persist_message_failed");
+        }
+        self.metrics.record_message_posted(&format!(
+            "{}::{}",
+            self.creator_address.to_hex_literal(),
+            self.collection_name
+        ));
+
+        let members = self.members.read().await;
+        for member_address in members.iter() {
+            self.send_targeted(*member_address, Output::Posted(message.clone()));
+        }
+    }
+
+    async fn tick_alive(&self) {
+        let alive_interval = if let Some(alive_interval) = self.alive_interval {
+            alive_interval
+        } else {
+            return;
+        };
+        loop {
+            time::sleep(alive_interval).await;
+            let members = self.members.read().await;
+            for member_address in members.iter() {
+                self.send_targeted(*member_address, Output::Alive);
+            }
+        }
+    }
+
+    fn send_targeted(&self, client_address: AccountAddress, output: Output) {
+        if self.output_sender.receiver_count() > 0 {
+            let _ = self
+                .output_sender
+                .send(OutputParcel::new(client_address, output));
+        }
+    }
+}
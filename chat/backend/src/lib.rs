@@ -0,0 +1,13 @@
+pub mod api;
+pub mod auth;
+pub mod client;
+pub mod cluster;
+pub mod hub;
+pub mod indexer;
+pub mod metrics;
+pub mod model;
+pub mod proto;
+pub mod response;
+pub mod server;
+pub mod storage;
+pub mod types;
@@ -1,6 +1,14 @@
 use clap::Parser;
+use reqwest::Url;
+use rusty_chat::cluster::{ClusterMetadata, NodeId};
+use rusty_chat::hub::DEFAULT_HISTORY_LIMIT;
+use rusty_chat::indexer::IndexerClient;
+use rusty_chat::metrics::Metrics;
 use rusty_chat::server::Server;
+use rusty_chat::storage::Storage;
 use aptos_logger::info;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone, Debug, Parser)]
 pub struct ServerArgs {
@@ -13,10 +21,76 @@ pub struct ServerArgs {
     listen_port: u16,
 }
 
+#[derive(Clone, Debug, Parser)]
+pub struct IndexerArgs {
+    #[clap(
+        long,
+        default_value = "https://indexer-testnet.staging.gcp.aptosdev.com/v1/graphql"
+    )]
+    indexer_url: Url,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct StorageArgs {
+    /// Where chat messages are persisted, so they can be replayed to clients on join.
+    #[clap(long, default_value = "sqlite://rusty-chat.db")]
+    database_url: String,
+
+    /// Default number of prior messages replayed to a client on join, overridable
+    /// per-join by the client.
+    #[clap(long, default_value_t = DEFAULT_HISTORY_LIMIT)]
+    history_limit: u32,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct ReverificationArgs {
+    /// How often, in seconds, to re-check every connected client's token ownership
+    /// against the indexer and evict anyone who no longer holds the room's token.
+    #[clap(long, default_value_t = 300)]
+    reverify_interval_secs: u64,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct AuthArgs {
+    /// The chain ID a client's signed `full_message` must embed to be accepted.
+    /// Defaults to testnet; repointing this server at a different network (e.g.
+    /// mainnet) requires setting this to match, or every signature will be rejected.
+    #[clap(long, default_value = "2")]
+    expected_chain_id: String,
+}
+
+#[derive(Clone, Debug, Parser)]
+pub struct ClusterArgs {
+    /// This node's own `ws://`/`wss://` base URL, as other nodes in the cluster
+    /// would reach it. Omit to run standalone, where this node owns every hub.
+    #[clap(long)]
+    this_node_url: Option<String>,
+
+    /// Base URLs of every node in the cluster, including this one. Ignored if
+    /// `this_node_url` isn't set.
+    #[clap(long)]
+    cluster_nodes: Vec<String>,
+}
+
 #[derive(Clone, Debug, Parser)]
 pub struct Args {
     #[clap(flatten)]
     server_args: ServerArgs,
+
+    #[clap(flatten)]
+    indexer_args: IndexerArgs,
+
+    #[clap(flatten)]
+    storage_args: StorageArgs,
+
+    #[clap(flatten)]
+    reverification_args: ReverificationArgs,
+
+    #[clap(flatten)]
+    auth_args: AuthArgs,
+
+    #[clap(flatten)]
+    cluster_args: ClusterArgs,
 }
 
 #[tokio::main]
@@ -28,9 +102,31 @@ async fn main() {
     let args = Args::parse();
     info!("Running with args: {:#?}", args);
 
+    let indexer_client = Arc::new(IndexerClient::new(args.indexer_args.indexer_url));
+    let storage = Arc::new(
+        Storage::connect(&args.storage_args.database_url)
+            .await
+            .expect("Failed to connect to storage"),
+    );
+    let metrics = Arc::new(Metrics::new().expect("Failed to register metrics"));
+    let cluster_metadata = match args.cluster_args.this_node_url {
+        Some(this_node_url) => ClusterMetadata::new(
+            NodeId(this_node_url),
+            args.cluster_args.cluster_nodes.into_iter().map(NodeId).collect(),
+        ),
+        None => ClusterMetadata::standalone(NodeId("local".to_string())),
+    };
+
     let server = Server::new(
         args.server_args.listen_address.clone(),
         args.server_args.listen_port,
+        indexer_client,
+        storage,
+        metrics,
+        cluster_metadata,
+        args.storage_args.history_limit,
+        Duration::from_secs(args.reverification_args.reverify_interval_secs),
+        args.auth_args.expected_chain_id,
     );
     server.run().await;
 }
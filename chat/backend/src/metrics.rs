@@ -0,0 +1,151 @@
+use prometheus::{
+    Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::time::Duration;
+
+/// The outcome of a single join attempt handled by `Server::handshake`, used to label
+/// `Metrics::join_attempts_total`. `Display` gives the exact label value recorded.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JoinOutcome {
+    /// The join request's public key or signature couldn't be decoded, or the indexer
+    /// lookup failed (`ApiErrors::BadRequest`). A handshake response that doesn't
+    /// parse at all is not counted under any outcome.
+    BadRequest,
+    /// The signature didn't verify, or the signed `full_message` had a missing or
+    /// mismatched address, chain ID, or nonce (`ApiErrors::NotRealAccountOwner`).
+    NotOwner,
+    /// The account doesn't hold the room's token (`ApiErrors::DoesNotHoldChatRoomToken`).
+    MissingToken,
+    Success,
+}
+
+impl JoinOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JoinOutcome::BadRequest => "bad_request",
+            JoinOutcome::NotOwner => "not_owner",
+            JoinOutcome::MissingToken => "missing_token",
+            JoinOutcome::Success => "success",
+        }
+    }
+}
+
+/// Process-wide Prometheus counters/gauges for connections, rooms, and message
+/// throughput, scraped by `Server`'s `/metrics` route. Cloning is cheap: every field
+/// is an `Arc`-backed handle into the same underlying registry.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    connections_total: IntCounter,
+    active_connections: IntGauge,
+    active_rooms: IntGauge,
+    messages_posted_total: IntCounterVec,
+    join_attempts_total: IntCounterVec,
+    auth_latency_seconds: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new();
+
+        let connections_total = IntCounter::new(
+            "chat_connections_total",
+            "Total number of client connections accepted since startup.",
+        )?;
+        let active_connections = IntGauge::new(
+            "chat_active_connections",
+            "Number of client connections currently open.",
+        )?;
+        let active_rooms = IntGauge::new(
+            "chat_active_rooms",
+            "Number of chat rooms (hubs) currently held in memory.",
+        )?;
+        let messages_posted_total = IntCounterVec::new(
+            Opts::new(
+                "chat_messages_posted_total",
+                "Total number of messages posted, labeled by room.",
+            ),
+            &["room"],
+        )?;
+        let join_attempts_total = IntCounterVec::new(
+            Opts::new(
+                "chat_join_attempts_total",
+                "Total number of join handshakes, labeled by outcome \
+                 (bad_request, not_owner, missing_token, success).",
+            ),
+            &["outcome"],
+        )?;
+        let auth_latency_seconds = Histogram::with_opts(HistogramOpts::new(
+            "chat_auth_latency_seconds",
+            "Time spent in authenticate_join_request per handshake, regardless of outcome.",
+        ))?;
+
+        registry.register(Box::new(connections_total.clone()))?;
+        registry.register(Box::new(active_connections.clone()))?;
+        registry.register(Box::new(active_rooms.clone()))?;
+        registry.register(Box::new(messages_posted_total.clone()))?;
+        registry.register(Box::new(join_attempts_total.clone()))?;
+        registry.register(Box::new(auth_latency_seconds.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            connections_total,
+            active_connections,
+            active_rooms,
+            messages_posted_total,
+            join_attempts_total,
+            auth_latency_seconds,
+        })
+    }
+
+    /// Called from `Server::process_client` once a client's handshake succeeds and
+    /// it's registered with its hub.
+    pub fn record_connected(&self) {
+        self.connections_total.inc();
+        self.active_connections.inc();
+    }
+
+    /// Called from `Server::process_client` once a connection's read/write loops
+    /// have exited, mirroring the `hub.on_disconnect` call it sits next to.
+    pub fn record_disconnected(&self) {
+        self.active_connections.dec();
+    }
+
+    /// Called from `Server::process_client` whenever a new hub is created for a room
+    /// that didn't already have one. Hubs are never dropped, so nothing decrements
+    /// this gauge.
+    pub fn record_room_created(&self) {
+        self.active_rooms.inc();
+    }
+
+    /// Called from `Hub::process_post` for every valid message posted, whether or not
+    /// persisting it succeeded. `room` is the hub's `(creator_address, collection_name)` storage
+    /// key, so throughput can be broken down per room rather than summed globally.
+    pub fn record_message_posted(&self, room: &str) {
+        self.messages_posted_total.with_label_values(&[room]).inc();
+    }
+
+    /// Called from `Server::handshake` once `authenticate_join_request` returns,
+    /// success or failure, to record which outcome the attempt landed on.
+    pub fn record_join_attempt(&self, outcome: JoinOutcome) {
+        self.join_attempts_total
+            .with_label_values(&[outcome.as_str()])
+            .inc();
+    }
+
+    /// Called from `Server::handshake` with how long `authenticate_join_request` took,
+    /// regardless of outcome.
+    pub fn record_auth_latency(&self, latency: Duration) {
+        self.auth_latency_seconds.observe(latency.as_secs_f64());
+    }
+
+    /// Renders the current values of all registered metrics in the Prometheus text
+    /// exposition format, for `Server`'s `/metrics` route to return as the response
+    /// body.
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let metric_families = self.registry.gather();
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&metric_families, &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
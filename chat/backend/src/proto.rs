@@ -0,0 +1,67 @@
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Input {
+    Post(PostInput),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PostInput {
+    pub body: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct InputParcel {
+    pub client_address: AccountAddress,
+    pub input: Input,
+}
+
+impl InputParcel {
+    pub fn new(client_address: AccountAddress, input: Input) -> Self {
+        InputParcel {
+            client_address,
+            input,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum Output {
+    Posted(PostedOutput),
+    History(HistoryOutput),
+    Alive,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PostedOutput {
+    pub address: AccountAddress,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Sent to a client right after it subscribes to a `Hub`, carrying whatever was
+/// recorded for that room before this connection joined. Analogous to CHATHISTORY in
+/// IRC servers, but unconditional and unpaginated: just the last `limit` messages.
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoryOutput {
+    pub messages: Vec<PostedOutput>,
+}
+
+#[derive(Clone, Debug)]
+pub struct OutputParcel {
+    pub client_address: AccountAddress,
+    pub output: Output,
+}
+
+impl OutputParcel {
+    pub fn new(client_address: AccountAddress, output: Output) -> Self {
+        OutputParcel {
+            client_address,
+            output,
+        }
+    }
+}
@@ -1,19 +1,32 @@
+use crate::api::{ApiErrors, HandshakeRequest, HandshakeResponse};
+use crate::auth::{
+    account_holds_chat_room_token, authenticate_join_request, new_nonce_store,
+    spawn_nonce_eviction_task, NonceStore,
+};
 use crate::client::Client;
+use crate::cluster::{ClusterClient, ClusterMetadata, NodeId};
 use crate::hub::{Hub, HubOptions};
+use crate::indexer::IndexerClient;
+use crate::metrics::{JoinOutcome, Metrics};
 use crate::proto::InputParcel;
+use crate::storage::Storage;
 use crate::types::HubId;
 use aptos_logger::{error, info};
 use aptos_sdk::types::account_address::AccountAddress;
-use futures::{StreamExt, TryStreamExt};
+use futures::{SinkExt, StreamExt, TryStreamExt};
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::{mpsc, RwLock};
+use tokio::time;
+use tokio::time::Instant;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use warp::ws::WebSocket;
+use uuid::Uuid;
+use warp::ws::{Message, WebSocket};
 use warp::Filter;
 
 const MAX_FRAME_SIZE: usize = 65536;
@@ -24,47 +37,164 @@ pub struct Server {
 
     // Map of HubId to Hub (aka chat room).
     hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+
+    indexer_client: Arc<IndexerClient>,
+
+    storage: Arc<Storage>,
+
+    metrics: Arc<Metrics>,
+
+    // Maps a hub to the node in the cluster that owns it. A standalone deployment
+    // (the default) owns every hub locally.
+    cluster_metadata: Arc<ClusterMetadata>,
+
+    // Used to bridge a client's connection through to the node that owns its hub,
+    // when that isn't this node.
+    cluster_client: Arc<ClusterClient>,
+
+    // Default number of prior messages replayed to a client on join, overridable
+    // per-join via `HandshakeResponse::history_limit`.
+    history_limit: u32,
+
+    // How often the background task in `spawn_reverification_task` re-checks every
+    // connected client's token ownership.
+    reverify_interval: Duration,
+
+    // Single-use nonces issued in `HandshakeRequest`s, consumed by `authenticate_join_request`.
+    nonces: NonceStore,
+
+    // The chain ID a client's signed `full_message` must embed; see `AuthArgs` in
+    // `main.rs`. Checked against the network this server is actually configured for,
+    // rather than hardcoded, so pointing it at a different network doesn't silently
+    // reject every valid signature.
+    expected_chain_id: String,
 }
 
 impl Server {
-    pub fn new(listen_address: String, listen_port: u16) -> Self {
+    pub fn new(
+        listen_address: String,
+        listen_port: u16,
+        indexer_client: Arc<IndexerClient>,
+        storage: Arc<Storage>,
+        metrics: Arc<Metrics>,
+        cluster_metadata: ClusterMetadata,
+        history_limit: u32,
+        reverify_interval: Duration,
+        expected_chain_id: String,
+    ) -> Self {
         Server {
             listen_address,
             listen_port,
             hubs: Arc::new(RwLock::new(HashMap::new())),
-            /*
-            hub: Arc::new(Hash::new(HubOptions {
-                alive_interval: Some(Duration::from_secs(5)),
-            })),
-            */
+            indexer_client,
+            storage,
+            metrics,
+            cluster_metadata: Arc::new(cluster_metadata),
+            cluster_client: Arc::new(ClusterClient::default()),
+            history_limit,
+            reverify_interval,
+            nonces: new_nonce_store(),
+            expected_chain_id,
         }
     }
 
     pub async fn run(&self) {
         let hubs = self.hubs.clone();
+        let indexer_client = self.indexer_client.clone();
+        let storage = self.storage.clone();
+        let metrics = self.metrics.clone();
+        let cluster_metadata = self.cluster_metadata.clone();
+        let cluster_client = self.cluster_client.clone();
+        let history_limit = self.history_limit;
+        let nonces = self.nonces.clone();
+        let expected_chain_id = self.expected_chain_id.clone();
+
+        Self::spawn_reverification_task(
+            self.hubs.clone(),
+            self.indexer_client.clone(),
+            self.reverify_interval,
+        );
+        spawn_nonce_eviction_task(self.nonces.clone());
 
         let chat = warp::path!("chat" / AccountAddress / String)
             .and(warp::ws())
             .and(warp::any().map(move || hubs.clone()))
+            .and(warp::any().map(move || indexer_client.clone()))
+            .and(warp::any().map(move || storage.clone()))
+            .and(warp::any().map(move || metrics.clone()))
+            .and(warp::any().map(move || cluster_metadata.clone()))
+            .and(warp::any().map(move || cluster_client.clone()))
+            .and(warp::any().map(move || nonces.clone()))
+            .and(warp::any().map(move || expected_chain_id.clone()))
             .map(
                 move |chat_room_creator: AccountAddress,
                       collection_name: String,
                       ws: warp::ws::Ws,
-                      hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>| {
+                      hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+                      indexer_client: Arc<IndexerClient>,
+                      storage: Arc<Storage>,
+                      metrics: Arc<Metrics>,
+                      cluster_metadata: Arc<ClusterMetadata>,
+                      cluster_client: Arc<ClusterClient>,
+                      nonces: NonceStore,
+                      expected_chain_id: String| {
                     let hub_id = HubId::new(chat_room_creator, collection_name);
                     ws.max_frame_size(MAX_FRAME_SIZE)
                         .on_upgrade(move |web_socket| async move {
-                            tokio::spawn(Self::process_client(hubs, hub_id, web_socket));
+                            // Keep the local fast-path unchanged when this node owns
+                            // the hub; only go through the cluster machinery when it
+                            // doesn't.
+                            if cluster_metadata
+                                .owns_locally(&hub_id.creator_address(), hub_id.collection_name())
+                            {
+                                tokio::spawn(Self::process_client(
+                                    hubs,
+                                    indexer_client,
+                                    storage,
+                                    metrics,
+                                    history_limit,
+                                    nonces,
+                                    expected_chain_id,
+                                    hub_id,
+                                    web_socket,
+                                ));
+                            } else {
+                                let owner = cluster_metadata
+                                    .owning_node(&hub_id.creator_address(), hub_id.collection_name())
+                                    .clone();
+                                tokio::spawn(Self::process_remote_client(
+                                    owner,
+                                    cluster_client,
+                                    hub_id,
+                                    web_socket,
+                                ));
+                            }
                         })
                 },
             );
 
+        let metrics = self.metrics.clone();
+        let metrics_route = warp::path!("metrics").and(warp::get()).map(move || {
+            match metrics.encode() {
+                Ok(body) => warp::reply::with_status(body, warp::http::StatusCode::OK),
+                Err(err) => {
+                    error!(event = "metrics_encode_failed", error = %err);
+                    warp::reply::with_status(
+                        String::new(),
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    )
+                }
+            }
+        });
+
+        let routes = chat.or(metrics_route);
+
         let shutdown = async {
             tokio::signal::ctrl_c()
                 .await
                 .expect("Failed to install CTRL+C signal handler");
         };
-        let (addr, serving) = warp::serve(chat).bind_with_graceful_shutdown(
+        let (addr, serving) = warp::serve(routes).bind_with_graceful_shutdown(
             (
                 IpAddr::from_str(&self.listen_address).expect("Listen address was invalid"),
                 self.listen_port,
@@ -79,32 +209,76 @@ impl Server {
 
     async fn process_client(
         hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+        indexer_client: Arc<IndexerClient>,
+        storage: Arc<Storage>,
+        metrics: Arc<Metrics>,
+        history_limit: u32,
+        nonces: NonceStore,
+        expected_chain_id: String,
         hub_id: HubId,
         web_socket: WebSocket,
     ) {
-        // At this point we have verified that the requester is truly the owner of the
-        // account that they say they are. Now we need to check if a Hub already exists
-        // for the chat they're trying to join. If not, we'll create one.
+        let (mut ws_sink, mut ws_stream) = web_socket.split();
+
+        // Before anything else, run the nonce handshake: issue a single-use nonce as
+        // the first clientbound frame, then require the join request's signed
+        // `full_message` to embed that exact nonce, so a `JoinChatRoomRequest`
+        // captured off the wire can't be replayed against a new connection.
+        let (client_address, requested_history_limit) = match Self::handshake(
+            &mut ws_sink,
+            &mut ws_stream,
+            indexer_client,
+            nonces,
+            &expected_chain_id,
+            &metrics,
+        )
+        .await
+        {
+                Ok(result) => result,
+                Err(err) => {
+                    error!(event = "handshake_failed", error = %err);
+                    let _ = ws_sink.close().await;
+                    return;
+                }
+            };
+
+        // Now we need to check if a Hub already exists for the chat they're trying to
+        // join. If not, we'll create one.
         let hub = {
             let mut hubs = hubs.write().await;
             if let Some(hub) = hubs.get(&hub_id) {
                 hub.clone()
             } else {
-                let hub = Arc::new(Hub::new(HubOptions {
-                    alive_interval: Some(Duration::from_secs(5)),
-                }));
+                let hub = Arc::new(Hub::new(
+                    &hub_id,
+                    storage.clone(),
+                    metrics.clone(),
+                    HubOptions {
+                        alive_interval: Some(Duration::from_secs(5)),
+                    },
+                ));
                 hubs.insert(hub_id.clone(), hub.clone());
+                metrics.record_room_created();
                 hub
             }
         };
 
         let (input_sender, input_receiver) = mpsc::unbounded_channel::<InputParcel>();
 
+        hub.register(client_address).await;
+
+        // Subscribe before replaying history: `send_history` broadcasts the history
+        // frame on the same `output_sender` as everything else, and a tokio broadcast
+        // channel only delivers to receivers that already exist at send time. Doing
+        // this the other way around silently drops the replay for a client with no
+        // other receiver yet subscribed.
         let output_receiver = hub.subscribe();
         let output_receiver = BroadcastStream::new(output_receiver);
-        let (ws_sink, ws_stream) = web_socket.split();
-        // TODO Use address from request.
-        let client = Client::new(AccountAddress::ZERO);
+
+        hub.send_history(client_address, requested_history_limit.unwrap_or(history_limit))
+            .await;
+        let client = Client::new(client_address);
+        metrics.record_connected();
 
         info!(address = client.address, event = "connected");
 
@@ -127,15 +301,155 @@ impl Server {
 
         let running_hub = hub.run(input_receiver);
 
+        // Races the read/write loops against a forced disconnect from the background
+        // reverification task, so a client that's had its access token revoked gets
+        // dropped immediately rather than only on its next message.
+        let mut disconnect_receiver = hub.subscribe_disconnects();
+        let kicked = async {
+            loop {
+                match disconnect_receiver.recv().await {
+                    Ok(address) if address == client.address => return,
+                    Ok(_) => continue,
+                    // Lagged just means this receiver missed some disconnects on a
+                    // channel of capacity 16, not that this specific client was
+                    // kicked; treating it as a kick would drop healthy clients
+                    // whenever >16 disconnects land in a burst. Only a closed
+                    // sender (the hub itself going away) is a real reason to stop.
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        };
+
         if let Err(err) = tokio::select! {
             result = reading => result,
             result = writing => result,
             _ = running_hub => Ok(()),
+            _ = kicked => {
+                info!(address = client.address, event = "kicked");
+                Ok(())
+            }
         } {
             error!("Client connection error: {}", err);
         }
 
         hub.on_disconnect(client.address).await;
+        metrics.record_disconnected();
         info!(address = client.address, event = "disconnected");
     }
+
+    /// The non-owning-node counterpart to `process_client`, for when this node
+    /// accepted a connection for a hub that `cluster_metadata` says is owned by
+    /// another node. Bridges the client's frames to and from a new connection this
+    /// node opens to the owning node's own `/chat` route, so the nonce handshake and
+    /// signed join request are handled end-to-end by the owner without this node
+    /// needing to understand them.
+    async fn process_remote_client(
+        owner: NodeId,
+        cluster_client: Arc<ClusterClient>,
+        hub_id: HubId,
+        web_socket: WebSocket,
+    ) {
+        info!(owner = owner.0, event = "bridging_to_owner");
+        if let Err(err) = cluster_client.bridge(&owner, &hub_id, web_socket).await {
+            error!(owner = owner.0, error = ?err, event = "bridge_failed");
+        }
+    }
+
+    // Periodically re-checks every connected client's token ownership against the
+    // indexer, since `authenticate_join_request` only verifies membership once at join
+    // time and these access tokens are transferable: a holder who sells or moves their
+    // NFT would otherwise keep their websocket open forever.
+    fn spawn_reverification_task(
+        hubs: Arc<RwLock<HashMap<HubId, Arc<Hub>>>>,
+        indexer_client: Arc<IndexerClient>,
+        reverify_interval: Duration,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                time::sleep(reverify_interval).await;
+
+                let live_hubs: Vec<Arc<Hub>> = hubs.read().await.values().cloned().collect();
+                for hub in live_hubs {
+                    let creator_address = hub.creator_address();
+                    let collection_name = hub.collection_name().to_string();
+                    for member_address in hub.members().await {
+                        let still_holds_token = account_holds_chat_room_token(
+                            &indexer_client,
+                            &member_address,
+                            &creator_address,
+                            &collection_name,
+                        )
+                        .await
+                        .unwrap_or(false);
+
+                        if !still_holds_token {
+                            info!(address = member_address, event = "revoking_membership");
+                            hub.kick(member_address).await;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Issues a single-use nonce as the first clientbound frame, then waits for the
+    // one and only frame the server expects before a connection has joined a room: a
+    // `HandshakeResponse` carrying a `JoinChatRoomRequest` whose signed `full_message`
+    // embeds that same nonce. Returns the authenticated joiner's account address,
+    // along with whatever history backfill size it asked for.
+    async fn handshake(
+        ws_sink: &mut (impl SinkExt<Message, Error = warp::Error> + Unpin),
+        ws_stream: &mut (impl futures::Stream<Item = Result<Message, warp::Error>> + Unpin),
+        indexer_client: Arc<IndexerClient>,
+        nonces: NonceStore,
+        expected_chain_id: &str,
+        metrics: &Arc<Metrics>,
+    ) -> anyhow::Result<(AccountAddress, Option<u32>)> {
+        let nonce = Uuid::new_v4().to_string();
+        nonces.write().await.insert(nonce.clone(), Instant::now());
+
+        let handshake_request = serde_json::to_string(&HandshakeRequest { nonce })?;
+        ws_sink.send(Message::text(handshake_request)).await?;
+
+        let message = ws_stream
+            .try_next()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Connection closed before completing the handshake"))?;
+        let handshake_response: HandshakeResponse = serde_json::from_str(
+            message
+                .to_str()
+                .map_err(|_| anyhow::anyhow!("Handshake response was not a text frame"))?,
+        )?;
+
+        // Timed (and outcome-counted) around `authenticate_join_request` specifically,
+        // rather than the whole handshake, since that's the call that actually talks to
+        // the indexer and is where a slowdown there would show up.
+        let auth_started_at = Instant::now();
+        let result = authenticate_join_request(
+            indexer_client,
+            nonces,
+            expected_chain_id,
+            &handshake_response.join_chat_room_request,
+        )
+        .await;
+        metrics.record_auth_latency(auth_started_at.elapsed());
+
+        let client_address = match result {
+            Ok(client_address) => {
+                metrics.record_join_attempt(JoinOutcome::Success);
+                client_address
+            }
+            Err(err) => {
+                metrics.record_join_attempt(match err {
+                    ApiErrors::BadRequest(_) => JoinOutcome::BadRequest,
+                    ApiErrors::NotRealAccountOwner(_) => JoinOutcome::NotOwner,
+                    ApiErrors::DoesNotHoldChatRoomToken(_) => JoinOutcome::MissingToken,
+                });
+                return Err(anyhow::Error::from(err));
+            }
+        };
+
+        Ok((client_address, handshake_response.history_limit))
+    }
 }
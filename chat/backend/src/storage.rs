@@ -0,0 +1,128 @@
+use crate::proto::PostedOutput;
+use anyhow::Context;
+use aptos_sdk::types::account_address::AccountAddress;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::Row;
+use std::str::FromStr;
+
+/// Persists every posted message to SQLite, keyed by the room's
+/// `(creator_address, collection_name)` pair, so `Server::process_client` can replay
+/// recent history to a client on join instead of it seeing an empty room.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    /// Opens (creating if necessary) the SQLite database at `database_url` and runs
+    /// migrations against it.
+    pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)
+            .context("Failed to parse database URL")?
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .context("Failed to connect to database")?;
+        let storage = Storage { pool };
+        storage.run_migrations().await?;
+        Ok(storage)
+    }
+
+    async fn run_migrations(&self) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS messages (
+                creator_address TEXT NOT NULL,
+                collection_name TEXT NOT NULL,
+                author_address TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create messages table")?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS messages_room_idx
+                ON messages (creator_address, collection_name, created_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create messages index")?;
+
+        Ok(())
+    }
+
+    /// Writes a message to storage. Called before broadcasting so a crash between the
+    /// write and the broadcast can never cause a message clients saw to go missing on
+    /// the next restart.
+    pub async fn record_message(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+        message: &PostedOutput,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO messages (creator_address, collection_name, author_address, body, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .bind(message.address.to_hex_literal())
+        .bind(&message.body)
+        .bind(message.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .context("Failed to record message")?;
+        Ok(())
+    }
+
+    /// Loads the most recent `limit` messages for a room, oldest first, for replay to
+    /// a client that just joined.
+    pub async fn load_recent_messages(
+        &self,
+        creator_address: &AccountAddress,
+        collection_name: &str,
+        limit: u32,
+    ) -> anyhow::Result<Vec<PostedOutput>> {
+        let rows = sqlx::query(
+            "SELECT author_address, body, created_at FROM messages \
+             WHERE creator_address = ?1 AND collection_name = ?2 \
+             ORDER BY created_at DESC LIMIT ?3",
+        )
+        .bind(creator_address.to_hex_literal())
+        .bind(collection_name)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to load recent messages")?;
+
+        let mut messages = Vec::with_capacity(rows.len());
+        for row in rows {
+            let author_address: String = row.try_get("author_address")?;
+            let body: String = row.try_get("body")?;
+            let created_at: String = row.try_get("created_at")?;
+
+            let address = AccountAddress::from_str(&author_address)
+                .context("Failed to parse stored author address")?;
+            let created_at: DateTime<Utc> = DateTime::parse_from_rfc3339(&created_at)
+                .context("Failed to parse stored created_at")?
+                .with_timezone(&Utc);
+
+            messages.push(PostedOutput {
+                address,
+                body,
+                created_at,
+            });
+        }
+
+        // We queried newest-first to make LIMIT cheap; replay should read oldest-first.
+        messages.reverse();
+        Ok(messages)
+    }
+}
@@ -18,4 +18,12 @@ impl HubId {
             collection_name,
         }
     }
+
+    pub fn creator_address(&self) -> AccountAddress {
+        self.creator_address
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
 }